@@ -0,0 +1,119 @@
+use std::env;
+use std::fs::{self, create_dir_all};
+use std::path::{Path, PathBuf};
+
+use crate::manifest::Manifest;
+use crate::parse_file::{parse_str, ParseError};
+use crate::save::file_name;
+
+/// Bundles every story file in the given directory into the binary being built. Call this from your build script.
+///
+/// Each ".txt" file in the directory, and its "story.ron" manifest if it has one, is parsed first,
+/// so a malformed story file fails the build with a ParseError
+/// instead of failing when the player reaches it. The bundle is written to OUT_DIR under the name given by [bundle_name()](fn.bundle_name.html),
+/// and its path is passed to the compiler in an environment variable named after the directory,
+/// where [include_story!](../macro.include_story.html) will find it when given the same directory, written the same way.
+///
+/// In the main function of build.rs:
+/// ```no_run
+/// intfic::embed::bundle_story("resources").unwrap_or_else(|e| panic!("{}", e));
+/// ```
+/// main.rs:
+/// ```ignore
+/// let loader: Loader = intfic::include_story!("resources");
+/// let mut engine: Engine = Engine::from_manifest(loader).unwrap();
+/// ```
+pub fn bundle_story<P: AsRef<Path>>(dir: P) -> Result<PathBuf, ParseError> {
+    let dir: &Path = dir.as_ref();
+    let out_dir: String = env::var("OUT_DIR").map_err(|e| ParseError::Io {
+        filename: dir.display().to_string(),
+        message: format!("OUT_DIR isn't set, bundle_story should be called from a build script: {}", e),
+    })?;
+
+    bundle_story_in(dir, Path::new(&out_dir))
+}
+
+/// Bundles every story file in the given directory like [bundle_story()](fn.bundle_story.html), but into the given output directory instead of OUT_DIR.
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use intfic::embed::bundle_story_in;
+/// let bundle = bundle_story_in(Path::new("resources"), Path::new("target/bundles")).unwrap();
+/// assert_eq!(bundle, Path::new("target/bundles/intfic_story/resources.rs"));
+/// ```
+pub fn bundle_story_in(dir: &Path, out_dir: &Path) -> Result<PathBuf, ParseError> {
+    let out_path: PathBuf = out_dir.join("intfic_story").join(bundle_name(dir));
+    let bundled: Vec<PathBuf> = write_bundle(dir, &out_path)?;
+
+    println!("cargo:rustc-env=INTFIC_STORY_{}={}", dir.display(), out_path.display());
+    println!("cargo:rerun-if-changed={}", dir.display());
+    for path in bundled.iter() {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    Ok(out_path)
+}
+
+/// Returns the file name the bundle for the given directory is written to in OUT_DIR.
+///
+/// The whole directory is made safe to use as one file name, so absolute directories, subdirectories,
+/// and directories outside the crate are all bundled inside OUT_DIR.
+///
+/// ```
+/// # use std::path::Path;
+/// # use intfic::embed::bundle_name;
+/// assert_eq!(bundle_name(Path::new("resources")), "resources.rs");
+/// assert_eq!(bundle_name(Path::new("story/act_1")), "story_act_1.rs");
+/// assert_eq!(bundle_name(Path::new("../stories")), "_stories.rs");
+/// ```
+pub fn bundle_name(dir: &Path) -> String {
+    format!("{}.rs", file_name(&dir.display().to_string()))
+}
+
+/// Parses every story file in the given directory and writes a bundle of them to the given path, returning the files bundled.
+///
+/// The bundle is a Rust expression of type `&[(&str, &str)]` pairing each filename with its contents,
+/// which are included with `include_str!` so the story files themselves are still tracked by cargo.
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use intfic::embed::write_bundle;
+/// let bundled = write_bundle(Path::new("resources"), Path::new("target/story_bundle.rs")).unwrap();
+/// assert!(bundled.contains(&Path::new("resources/example_1.txt").to_path_buf()));
+/// ```
+pub fn write_bundle(dir: &Path, out_path: &Path) -> Result<Vec<PathBuf>, ParseError> {
+    let io_error = |filename: &Path, e: std::io::Error| ParseError::Io {
+        filename: filename.display().to_string(),
+        message: e.to_string(),
+    };
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| io_error(dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().and_then(|ext| ext.to_str()) == Some("txt")
+                || path.file_name().and_then(|name| name.to_str()) == Some(Manifest::FILENAME)
+        })
+        .collect();
+    paths.sort();
+
+    let mut bundle = String::from("&[\n");
+    for path in paths.iter() {
+        let filename: String = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let contents: String = fs::read_to_string(path).map_err(|e| io_error(path, e))?;
+        if filename == Manifest::FILENAME {
+            Manifest::parse(&contents)?;
+        } else {
+            parse_str(&filename, &contents)?;
+        }
+
+        let full_path: PathBuf = fs::canonicalize(path).map_err(|e| io_error(path, e))?;
+        bundle.push_str(&format!("    ({:?}, include_str!({:?})),\n", filename, full_path.display().to_string()));
+    }
+    bundle.push(']');
+
+    if let Some(parent) = out_path.parent() {
+        create_dir_all(parent).map_err(|e| io_error(parent, e))?;
+    }
+    fs::write(out_path, bundle).map_err(|e| io_error(out_path, e))?;
+    Ok(paths)
+}
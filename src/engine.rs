@@ -0,0 +1,597 @@
+use std::collections::VecDeque;
+
+use crate::frontend::Frontend;
+use crate::game_state::GameState;
+use crate::manifest::Manifest;
+use crate::parse_file::{Loader, ParseError};
+use crate::parse_input::{check_keywords, sanitize};
+use crate::print_debug;
+use crate::save::{Migration, SlotInfo};
+use crate::story::{split_target, Story};
+use crate::story_block::{available_choices, Choice, Next, Prompt, Statement, StoryBlock};
+use crate::write_out::Color;
+
+// The most blocks read one after another through lone options before the Engine decides they lead in a circle.
+const FOLLOW_LIMIT: usize = 100;
+
+/// Something the story wants the player to see.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A line of story text and the color it should be written in.
+    Line(String, Color),
+    /// An empty line, separating blocks and questions from the rest of the text.
+    Break,
+    /// The text of each option the player may choose from, in the order they should be numbered.
+    Options(Vec<String>),
+    /// A question the player should answer in their own words with their next input.
+    Prompt(String),
+    /// The story has reached a block with nowhere left to go.
+    End,
+}
+
+/// The events produced by a single step of the Engine, in the order they should be displayed.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Output {
+    /// Everything that happened since the last step.
+    pub events: Vec<Event>,
+}
+
+/// Plays through a story one step at a time, so the program hosting it stays in control of input and output.
+///
+/// Each call to [start](#method.start), [step](#method.step), or [resume](#method.resume) reads blocks until the player
+/// has a choice to make or the story ends, and returns the resulting [Output](struct.Output.html).
+///
+/// ```no_run
+/// # use intfic::engine::{Engine, Event};
+/// # use intfic::game_state::GameState;
+/// let mut game: GameState = GameState::new("Test GameState");
+/// game.set_progress("example_1.txt", "start");
+///
+/// let mut engine: Engine = Engine::new(game);
+/// let output = engine.start();
+/// assert!(output.events.contains(&Event::Options(vec![
+///     String::from("Yeah I guess."),
+///     String::from("Not really."),
+/// ])));
+///
+/// engine.step("yeah");
+/// ```
+#[derive(Debug, Default)]
+pub struct Engine {
+    /// The GameState being played through.
+    pub game: GameState,
+    /// Where story files are loaded from when the story reaches one that hasn't been parsed yet.
+    pub loader: Loader,
+    /// Every story file parsed so far, which all blocks are looked up in.
+    pub story: Story,
+    /// Applied, in order, to saves made with a different version of the story when the player loads them.
+    /// See [Migration](../save/struct.Migration.html)
+    pub migrations: Vec<Migration>,
+    start: (String, String),
+    choices: Vec<Choice>,
+    pending: VecDeque<Statement>,
+    offered: Vec<Choice>,
+    prompt: Option<Prompt>,
+    finished: bool,
+}
+
+impl Engine {
+    /// Creates an Engine that will play the given GameState, starting from its progress.
+    ///
+    /// ```
+    /// # use intfic::engine::Engine;
+    /// # use intfic::game_state::GameState;
+    /// let engine: Engine = Engine::new(GameState::new("Test GameState"));
+    ///
+    /// assert_eq!(engine.game.name, String::from("Test GameState"));
+    /// assert_eq!(engine.is_finished(), false);
+    /// ```
+    pub fn new(game: GameState) -> Engine {
+        Engine::with_loader(game, Loader::default())
+    }
+
+    /// Creates an Engine that will play the given GameState, loading story files with the given Loader.
+    ///
+    /// ```
+    /// # use intfic::engine::{Engine, Event};
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::write_out::Color;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("start.txt", "start");
+    ///
+    /// let loader: Loader = Loader::from_files(vec![("start.txt", ":- start\nHello!")]);
+    /// let mut engine: Engine = Engine::with_loader(game, loader);
+    ///
+    /// assert_eq!(engine.start().events[0], Event::Line(String::from("Hello!"), Color::White));
+    /// ```
+    pub fn with_loader(game: GameState, loader: Loader) -> Engine {
+        Engine {
+            start: game.progress.clone(),
+            game,
+            loader,
+            ..Engine::default()
+        }
+    }
+
+    /// Creates an Engine for the story the given Loader finds files in, starting a new GameState from its manifest.
+    ///
+    /// The starting story file and every file listed in the manifest are parsed up front, along with the files they lead to,
+    /// so a ParseError is returned if any of them can't be loaded. The Loader is given the manifest's declared variables,
+    /// so story files that change undeclared flags or counters can't be loaded either.
+    /// The GameState is given a fingerprint of every file parsed, so saves made with other versions of the story can be told apart.
+    /// See [Manifest](../manifest/struct.Manifest.html) for what goes in "story.ron".
+    ///
+    /// ```
+    /// # use intfic::engine::{Engine, Event};
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::write_out::Color;
+    /// let loader: Loader = Loader::from_files(vec![
+    ///     ("story.ron", "(title: \"Test Story\", start: (\"start.txt\", \"start\"))"),
+    ///     ("start.txt", ":- start\nHello!"),
+    /// ]);
+    /// let mut engine: Engine = Engine::from_manifest(loader).unwrap();
+    ///
+    /// assert_eq!(engine.game.name, String::from("Test Story"));
+    /// assert_eq!(engine.start().events[0], Event::Line(String::from("Hello!"), Color::White));
+    /// ```
+    pub fn from_manifest(mut loader: Loader) -> Result<Engine, ParseError> {
+        let manifest: Manifest = Manifest::load(&loader)?;
+        loader.variables = manifest.variables.clone();
+
+        let mut story: Story = Story::load(&loader, &manifest.files)?;
+        story.add(&loader, &manifest.start.0)?;
+
+        let mut game: GameState = GameState::from_manifest(&manifest);
+        let files: Vec<&str> = story.files().collect();
+        game.story_hash = loader.fingerprint(&files);
+
+        let mut engine: Engine = Engine::with_loader(game, loader);
+        engine.story = story;
+        Ok(engine)
+    }
+
+    /// Returns true once the story has ended and there are no more choices to make.
+    ///
+    /// ```no_run
+    /// # use intfic::engine::Engine;
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("example_1.txt", "end");
+    ///
+    /// let mut engine: Engine = Engine::new(game);
+    /// engine.start();
+    /// assert_eq!(engine.is_finished(), true);
+    /// ```
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Returns true while the player is being asked a question to answer in their own words, rather than choosing an option.
+    ///
+    /// ```
+    /// # use intfic::engine::{Engine, Event};
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::parse_file::Loader;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("start.txt", "start");
+    ///
+    /// let loader: Loader = Loader::from_files(vec![("start.txt", ":- start\n$- player_name => What's your name?")]);
+    /// let mut engine: Engine = Engine::with_loader(game, loader);
+    ///
+    /// assert!(engine.start().events.contains(&Event::Prompt(String::from("What's your name?"))));
+    /// assert_eq!(engine.is_prompting(), true);
+    /// engine.step("Sam");
+    /// assert_eq!(engine.is_prompting(), false);
+    /// assert_eq!(engine.game.get_string("player_name"), "Sam");
+    /// ```
+    pub fn is_prompting(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    /// Plays the story from our GameState's progress until it ends or the Frontend runs out of input.
+    ///
+    /// Input is checked for keywords such as "save" and "load" before being matched against the options presented.
+    /// See [check_keywords()](../parse_input/fn.check_keywords.html) for the full list.
+    /// Answers to questions the player is asked are taken as they are, without checking for keywords.
+    /// If the player quits, the story ends with [Event::End](enum.Event.html#variant.End) and this returns,
+    /// leaving it to the caller whether to exit.
+    ///
+    /// ```
+    /// # use intfic::engine::Engine;
+    /// # use intfic::frontend::Mock;
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("test.txt", "start");
+    ///
+    /// let mut engine: Engine = Engine::new(game);
+    /// let mut mock: Mock = Mock::new(&["2"]);
+    /// engine.run(&mut mock);
+    ///
+    /// assert!(mock.transcript.contains(&String::from("This is test 5!")));
+    /// assert_eq!(engine.is_finished(), true);
+    /// ```
+    pub fn run(&mut self, frontend: &mut dyn Frontend) {
+        frontend.show(&self.start());
+
+        while !self.finished {
+            let input: String = match frontend.read_input() {
+                Some(input) => input,
+                None => break,
+            };
+
+            let output = if self.is_prompting() {
+                self.step(&input)
+            } else {
+                match check_keywords(sanitize(input.clone()), &mut self.game, frontend) {
+                    Some(_) => self.step(&input),
+                    None if self.game.quitting => self.quit(),
+                    None => self.resume(),
+                }
+            };
+            frontend.show(&output);
+        }
+    }
+
+    /// Finds the story file and block indicated in our GameState's progress, and reads until the player has a choice to make.
+    ///
+    /// If the story file hasn't been parsed yet, it's loaded along with every story file it leads to.
+    /// An empty block name starts from the first block of the file.
+    /// 
+    /// If our GameState was just loaded from a save made with a different version of the story, our
+    /// [migrations](#structfield.migrations) are applied to it first. If the save's block is no longer in the story,
+    /// the player is warned and the story carries on from the most recent of its [checkpoints](../game_state/struct.GameState.html#structfield.checkpoints)
+    /// that still is, or else the start of its story file, or else where this Engine started.
+    ///
+    /// ```no_run
+    /// # use intfic::engine::Engine;
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("example_1.txt", "start");
+    ///
+    /// let mut engine: Engine = Engine::new(game);
+    /// let output = engine.start();
+    /// ```
+    pub fn start(&mut self) -> Output {
+        let mut events: Vec<Event> = Vec::new();
+
+        self.finished = false;
+        self.pending.clear();
+        self.offered.clear();
+        self.prompt = None;
+        if let Some(next) = self.locate(&mut events) {
+            self.play(next, &mut events);
+        } else {
+            self.finish(&mut events);
+        }
+
+        self.output(events)
+    }
+
+    /// Matches the player's input against the options presented, and reads on from the chosen one.
+    ///
+    /// If no option matches, the options stay available and the player is told their input wasn't understood.
+    /// While the player is being asked a question, their input is checked against its rules and stored as their answer instead.
+    /// Blocks whose lone options lead in a circle are read a limited number of times before the story ends with a red line explaining why.
+    ///
+    /// ```no_run
+    /// # use intfic::engine::Engine;
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("example_1.txt", "start");
+    ///
+    /// let mut engine: Engine = Engine::new(game);
+    /// engine.start();
+    /// engine.step("1");
+    /// assert_eq!(engine.game.progress.1, String::from("flag_example"));
+    /// ```
+    pub fn step(&mut self, input: &str) -> Output {
+        let mut events: Vec<Event> = Vec::new();
+
+        if let Some(prompt) = &self.prompt {
+            match prompt.validate(input) {
+                Ok(answer) => {
+                    self.game.set_string(&prompt.variable, &answer);
+                    self.prompt = None;
+                    self.proceed(&mut events);
+                }
+                Err(message) => {
+                    events.push(Event::Line(message, Color::Red));
+                    events.push(Event::Prompt(prompt.filled_question(&self.game)));
+                }
+            }
+            return self.output(events);
+        }
+
+        let input: String = sanitize(String::from(input));
+
+        if self.finished || input.is_empty() {
+            return self.output(events);
+        }
+
+        let chosen = self
+            .choices
+            .iter()
+            .enumerate()
+            .find(|(i, choice)| choice.matches(&input, i + 1))
+            .map(|(_, choice)| choice.clone());
+
+        if let Some(choice) = chosen {
+            self.choices.clear();
+            choice.choose(&mut self.game);
+            let result: String = String::from(choice.target(&self.game));
+            if let Some(next) = self.resolve(&result, &mut events) {
+                self.play(next, &mut events);
+            } else {
+                self.finish(&mut events);
+            }
+        } else {
+            events.push(Event::Line(String::from("I didn't understand that."), Color::White));
+        }
+
+        self.output(events)
+    }
+
+    /// Presents the block indicated in our GameState's progress again, without applying its effects a second time.
+    ///
+    /// Its options are shown again without choosing any of them, even if only one is available,
+    /// so no option's effects are applied and the story doesn't move on until the player chooses.
+    ///
+    /// This is useful after saving or loading, when the player should be reminded of where they are.
+    /// Like [start](#method.start), a GameState that was just loaded is checked against the story first.
+    /// If the player was being asked a question, they're just asked it again, and the rest of the block carries on once they answer.
+    ///
+    /// ```no_run
+    /// # use intfic::engine::Engine;
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("example_1.txt", "start");
+    ///
+    /// let mut engine: Engine = Engine::new(game);
+    /// assert_eq!(engine.start(), engine.resume());
+    /// ```
+    pub fn resume(&mut self) -> Output {
+        let mut events: Vec<Event> = Vec::new();
+
+        // A question asked before a save was loaded has nothing to do with the loaded game
+        if self.game.loaded.is_some() {
+            self.prompt = None;
+            self.pending.clear();
+        }
+        if let Some(prompt) = &self.prompt {
+            events.push(Event::Prompt(prompt.filled_question(&self.game)));
+            return self.output(events);
+        }
+
+        self.finished = false;
+        self.choices.clear();
+        if let Some((story, name)) = self.locate(&mut events) {
+            let block: &StoryBlock = self.story.get(&story, &name).expect("found blocks are in the story");
+            block.read_text(&self.game, &mut events);
+            let options: Vec<Choice> = block.available_options(&self.game);
+            self.present(options, &mut events);
+        } else {
+            self.finish(&mut events);
+        }
+
+        self.output(events)
+    }
+
+    // Reads blocks starting from the given story file and block, following lone options, until the player has a choice to make or the story ends.
+    fn play(&mut self, next: (String, String), events: &mut Vec<Event>) {
+        self.enter(next);
+        self.proceed(events);
+    }
+
+    // Moves our GameState's progress to the given block, and queues up its body to be carried out.
+    fn enter(&mut self, (story, name): (String, String)) {
+        let block: &StoryBlock = self.story.get(&story, &name).expect("found blocks are in the story");
+        self.pending = block.body.iter().cloned().collect();
+        self.offered.clear();
+        self.game.visit(&story, &name);
+        self.game.progress = (story, name);
+    }
+
+    // Carries out the rest of the current block's body, stopping to ask the player any question it reaches,
+    // then offers the options it reached and reads on through blocks with a single option until the player has a choice to make.
+    //
+    // Lone options that lead in a circle would keep it reading forever, so it gives up and ends the story after too many blocks in a row.
+    fn proceed(&mut self, events: &mut Vec<Event>) {
+        let mut followed: usize = 0;
+        loop {
+            while let Some(statement) = self.pending.pop_front() {
+                match statement.execute(&mut self.game, events) {
+                    Next::Continue => (),
+                    Next::Ask(prompt) => {
+                        events.push(Event::Prompt(prompt.filled_question(&self.game)));
+                        self.prompt = Some(prompt.clone());
+                        return;
+                    }
+                    Next::Enter(branch) => {
+                        for statement in branch.iter().rev() {
+                            self.pending.push_front(statement.clone());
+                        }
+                    }
+                    Next::Offer(choice) => self.offered.push(choice.clone()),
+                }
+            }
+            events.push(Event::Break);
+
+            let options: Vec<Choice> = available_choices(&self.offered, &self.game);
+            self.offered.clear();
+            match self.offer(options, events) {
+                Some(next) if followed < FOLLOW_LIMIT => {
+                    followed += 1;
+                    self.enter(next);
+                }
+                Some((story, name)) => {
+                    events.push(Event::Line(
+                        format!("Stopped after reading {} blocks without a choice, the options leading to {}#{} may go in a circle", FOLLOW_LIMIT, story, name),
+                        Color::Red,
+                    ));
+                    self.finish(events);
+                    return;
+                }
+                None => return,
+            }
+        }
+    }
+
+    // Presents the given options to the player, or returns the story file and block to read next if there is no choice to make.
+    fn offer(&mut self, options: Vec<Choice>, events: &mut Vec<Event>) -> Option<(String, String)> {
+        match options.len() {
+            0 => {
+                self.finish(events);
+                None
+            }
+            1 => {
+                options[0].choose(&mut self.game);
+                let result: String = String::from(options[0].target(&self.game));
+                let next = self.resolve(&result, events);
+                if next.is_none() {
+                    self.finish(events);
+                }
+                next
+            }
+            _ => {
+                self.present(options, events);
+                None
+            }
+        }
+    }
+
+    // Shows the given options to the player without choosing any of them, even if there's only one, or ends the story if there are none.
+    fn present(&mut self, options: Vec<Choice>, events: &mut Vec<Event>) {
+        if options.is_empty() {
+            self.finish(events);
+        } else {
+            events.push(Event::Options(options.iter().map(|choice| choice.text.clone()).collect()));
+            self.choices = options;
+        }
+    }
+
+    // Finds the story file and block an option's result points to, loading a new story file if needed.
+    //
+    // Moving on to another block means there's progress that hasn't been saved, whichever file the block is in.
+    fn resolve(&mut self, result: &str, events: &mut Vec<Event>) -> Option<(String, String)> {
+        let next = if let Some((story, name)) = split_target(result) {
+            self.find(story, name, events)
+        } else if self.story.get(&self.game.progress.0, result).is_some() {
+            Some((self.game.progress.0.clone(), String::from(result)))
+        } else {
+            print_debug(format!("Can't find StoryBlock: {}", result));
+            None
+        };
+
+        if next.is_some() {
+            self.game.set_flag("saved", false);
+        }
+        next
+    }
+
+    // Finds the block our GameState's progress points to. If it was just loaded from a save, the save is brought up to date
+    // with the story first, and if its block is gone the nearest checkpoint still in the story is used instead.
+    fn locate(&mut self, events: &mut Vec<Event>) -> Option<(String, String)> {
+        let loaded: Option<SlotInfo> = self.game.loaded.take();
+
+        if let Some(info) = &loaded {
+            if info.story_hash == 0 || info.story_hash != self.game.story_hash {
+                for migration in self.migrations.iter() {
+                    migration.apply(&mut self.game);
+                }
+            }
+            if info.story_hash != 0 && self.game.story_hash != 0 && info.story_hash != self.game.story_hash {
+                let warning: String = format!("\"{}\" was saved with a different version of the story, so some things may have changed.", info.slot);
+                events.push(Event::Line(warning, Color::Yellow));
+            }
+        }
+
+        let (story, name) = self.game.progress.clone();
+        if loaded.is_none() || self.exists(&story, &name) {
+            return self.find(&story, &name, events);
+        }
+
+        let mut candidates: Vec<(String, String)> = self
+            .game
+            .checkpoints
+            .iter()
+            .rev()
+            .filter_map(|checkpoint| split_target(checkpoint))
+            .map(|(story, name)| (String::from(story), String::from(name)))
+            .collect();
+        candidates.push((story, String::new()));
+        candidates.push(self.start.clone());
+
+        let saved_at: String = self.game.progress_ref();
+        match candidates.into_iter().find(|(story, name)| self.exists(story, name)) {
+            Some((story, name)) => {
+                self.game.progress = (story.clone(), name.clone());
+                let warning: String = format!("{} is no longer in the story, so you'll carry on from {} instead.", saved_at, self.game.progress_ref());
+                events.push(Event::Line(warning, Color::Red));
+                self.find(&story, &name, events)
+            }
+            None => {
+                events.push(Event::Line(format!("{} is no longer in the story, and there's nowhere to carry on from.", saved_at), Color::Red));
+                None
+            }
+        }
+    }
+
+    // Returns true if the given block of the given story file is in the story, loading the file if it needs to be.
+    fn exists(&mut self, story: &str, name: &str) -> bool {
+        self.story.add(&self.loader, story).is_ok() && self.story.find(story, name).is_some()
+    }
+
+    // Finds the block with the given name in the given story file, or its first block if the name is empty.
+    fn find(&mut self, story: &str, name: &str, events: &mut Vec<Event>) -> Option<(String, String)> {
+        if !self.load(story, events) {
+            return None;
+        }
+
+        let block: Option<&StoryBlock> = self.story.find(story, name);
+        if block.is_none() {
+            print_debug(format!("No block found with the name {}", name));
+        }
+        block.map(|block| (String::from(story), block.name.clone()))
+    }
+
+    // Parses the given story file into our Story if it isn't there already, along with every story file it leads to.
+    //
+    // If the file can't be loaded, the problem is shown to the player instead.
+    fn load(&mut self, filename: &str, events: &mut Vec<Event>) -> bool {
+        match self.story.add(&self.loader, filename) {
+            Ok(()) => true,
+            Err(e) => {
+                events.push(Event::Line(e.to_string(), Color::Red));
+                false
+            }
+        }
+    }
+
+    // Wraps up the events produced by a step of the story, remembering the last line of text shown in our GameState for save slots.
+    fn output(&mut self, events: Vec<Event>) -> Output {
+        let last_line = events.iter().rev().find_map(|event| match event {
+            Event::Line(line, _) if !line.trim().is_empty() => Some(line),
+            _ => None,
+        });
+        if let Some(line) = last_line {
+            self.game.excerpt = String::from(line.trim());
+        }
+
+        Output { events }
+    }
+
+    // Ends the story because the player quit.
+    fn quit(&mut self) -> Output {
+        let mut events: Vec<Event> = Vec::new();
+        self.finish(&mut events);
+        self.output(events)
+    }
+
+    // Marks the story as over.
+    fn finish(&mut self, events: &mut Vec<Event>) {
+        self.finished = true;
+        self.choices.clear();
+        events.push(Event::End);
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::game_state::GameState;
+
+/// An operator joining two expressions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`, rounding towards zero. Dividing by zero gives zero.
+    Div,
+    /// `%`, the remainder of dividing. Dividing by zero gives zero.
+    Rem,
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `and` or `&&`
+    And,
+    /// `or` or `||`
+    Or,
+}
+
+/// A condition or calculation written in a story file, such as `has_key and not door_open` or `strength > enemy_strength + 2`.
+///
+/// Variables are read from the GameState according to how they are used. A variable used as a number,
+/// such as either side of `>` or `+`, is a counter. A variable used as a truth value,
+/// such as either side of `and` or the whole of a condition, is a flag.
+/// `==` and `!=` compare truth values instead of numbers if either side is `true`, `false`, or a condition,
+/// so `has_key == false` checks a flag.
+///
+/// From loosest to tightest, expressions are built from:
+/// * `or` / `||`
+/// * `and` / `&&`
+/// * `not` / `!`
+/// * `==`, `!=`, `<`, `<=`, `>`, `>=`
+/// * `+`, `-`
+/// * `*`, `/`, `%`
+/// * `-` in front of a number
+/// * whole numbers, `true`, `false`, variable names, visits, and expressions in parentheses
+///
+/// The story keeps count of how many times each block has been read, which conditions can check with:
+/// * `visits(cellar)`, the number of times the "cellar" block of the current story file has been read.
+///   A block in another story file is written like `visits(chapter2.txt#cellar)`.
+/// * `visited(cellar)`, true if the block has been read at least once.
+/// * `first_visit`, true if the block being read is being read for the first time.
+///
+/// ```
+/// # use intfic::expr::Expr;
+/// # use intfic::game_state::GameState;
+/// let mut game: GameState = GameState::new("Test GameState");
+/// game.set_flag("has_key", true);
+/// game.update_counter("strength", 30);
+///
+/// assert!(Expr::parse("has_key and not door_open").unwrap().is_true(&game));
+/// assert!(Expr::parse("strength > enemy_strength + 2").unwrap().is_true(&game));
+/// assert_eq!(Expr::parse("(strength - 10) / 4").unwrap().value(&game), 5);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A whole number.
+    Int(i32),
+    /// `true` or `false`.
+    Bool(bool),
+    /// The name of a flag or counter.
+    Var(String),
+    /// `not` or `!` in front of an expression.
+    Not(Box<Expr>),
+    /// `-` in front of an expression.
+    Neg(Box<Expr>),
+    /// `visits(block)`, the number of times a block has been read. An empty block name is the block being read.
+    ///
+    /// `visited(block)` is parsed as `visits(block) > 0`, and `first_visit` as `visits() == 1` for the block being read.
+    Visits(String),
+    /// Two expressions joined by an operator.
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+/// A problem found while parsing an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    /// The byte offset within the expression the problem was found at.
+    pub offset: usize,
+    /// A description of what went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ExprError {}
+
+impl Expr {
+    /// Parses an expression, returning an ExprError pointing at the problem if it's malformed.
+    ///
+    /// ```
+    /// # use intfic::expr::{Expr, ExprError, Op};
+    /// assert_eq!(
+    ///     Expr::parse("not has_key").unwrap(),
+    ///     Expr::Not(Box::new(Expr::Var(String::from("has_key")))),
+    /// );
+    /// assert_eq!(
+    ///     Expr::parse("strength >").unwrap_err(),
+    ///     ExprError { offset: 10, message: String::from("expected a number, variable, or `(`, found the end of the condition") },
+    /// );
+    /// ```
+    pub fn parse(text: &str) -> Result<Expr, ExprError> {
+        let mut parser: Parser = Parser { tokens: tokenize(text)?, pos: 0, len: text.len() };
+        let expr: Expr = parser.or()?;
+
+        match parser.peek() {
+            Some((offset, token)) => Err(ExprError { offset, message: format!("expected an operator, found `{}`", token) }),
+            None => Ok(expr),
+        }
+    }
+
+    /// Evaluates this expression as a truth value in the given GameState.
+    ///
+    /// Variables are read as flags, and numbers are true unless they're zero.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// let expr: Expr = Expr::parse("has_key || score >= 50").unwrap();
+    ///
+    /// assert_eq!(expr.is_true(&game), false);
+    /// game.add_score(50);
+    /// assert_eq!(expr.is_true(&game), true);
+    /// ```
+    pub fn is_true(&self, game: &GameState) -> bool {
+        match self {
+            Expr::Bool(b) => *b,
+            Expr::Var(name) => game.get_flag(name),
+            Expr::Not(expr) => !expr.is_true(game),
+            Expr::Binary(left, Op::And, right) => left.is_true(game) && right.is_true(game),
+            Expr::Binary(left, Op::Or, right) => left.is_true(game) || right.is_true(game),
+            Expr::Binary(left, op, right) if self.compares_truth() => {
+                (left.is_true(game) == right.is_true(game)) == (*op == Op::Eq)
+            }
+            Expr::Binary(left, op, right) if op.is_comparison() => op.compare(left.value(game), right.value(game)),
+            _ => self.value(game) != 0,
+        }
+    }
+
+    /// Evaluates this expression as a whole number in the given GameState.
+    ///
+    /// Variables are read as counters, and truth values are 1 if true and 0 if false.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.update_counter("gold", 7);
+    ///
+    /// assert_eq!(Expr::parse("gold * 2 - 1").unwrap().value(&game), 13);
+    /// assert_eq!(Expr::parse("gold % 0").unwrap().value(&game), 0);
+    /// ```
+    pub fn value(&self, game: &GameState) -> i32 {
+        match self {
+            Expr::Int(n) => *n,
+            Expr::Var(name) => game.get_counter(name),
+            Expr::Visits(block) => i32::try_from(game.get_visits(block)).unwrap_or(i32::MAX),
+            Expr::Neg(expr) => expr.value(game).wrapping_neg(),
+            Expr::Binary(left, op, right) if op.is_arithmetic() => op.calculate(left.value(game), right.value(game)),
+            _ => self.is_true(game) as i32,
+        }
+    }
+
+    /// Returns the names of every flag this expression reads, in alphabetical order.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// let expr: Expr = Expr::parse("has_key and strength > enemy_strength").unwrap();
+    ///
+    /// assert_eq!(expr.flags(), vec!["has_key"]);
+    /// ```
+    pub fn flags(&self) -> Vec<&str> {
+        let mut flags: BTreeSet<&str> = BTreeSet::new();
+        self.variables(true, &mut flags, &mut BTreeSet::new());
+        flags.into_iter().collect()
+    }
+
+    /// Returns the names of every counter this expression reads, in alphabetical order.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// let expr: Expr = Expr::parse("has_key and strength > enemy_strength").unwrap();
+    ///
+    /// assert_eq!(expr.counters(), vec!["enemy_strength", "strength"]);
+    /// ```
+    pub fn counters(&self) -> Vec<&str> {
+        let mut counters: BTreeSet<&str> = BTreeSet::new();
+        self.variables(true, &mut BTreeSet::new(), &mut counters);
+        counters.into_iter().collect()
+    }
+
+    /// Returns the names of every flag and every counter this expression reads when it's calculated as a number,
+    /// such as the value of a counter update, each in alphabetical order.
+    ///
+    /// A name on its own is read as a counter here, rather than as a flag like [flags()](#method.flags) and [counters()](#method.counters) read it.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// assert_eq!(Expr::parse("stash").unwrap().value_variables(), (vec![], vec!["stash"]));
+    /// assert_eq!(Expr::parse("(has_key == true) * bonus").unwrap().value_variables(), (vec!["has_key"], vec!["bonus"]));
+    /// ```
+    pub fn value_variables(&self) -> (Vec<&str>, Vec<&str>) {
+        let mut flags: BTreeSet<&str> = BTreeSet::new();
+        let mut counters: BTreeSet<&str> = BTreeSet::new();
+        self.variables(false, &mut flags, &mut counters);
+        (flags.into_iter().collect(), counters.into_iter().collect())
+    }
+
+    /// Returns every block whose visits this expression reads, as written, in alphabetical order.
+    ///
+    /// The block being read, which `first_visit` checks, isn't included.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// let expr: Expr = Expr::parse("visited(cellar) and visits(chapter2.txt#attic) > 2 and first_visit").unwrap();
+    ///
+    /// assert_eq!(expr.blocks(), vec!["cellar", "chapter2.txt#attic"]);
+    /// ```
+    pub fn blocks(&self) -> Vec<&str> {
+        let mut blocks: BTreeSet<&str> = BTreeSet::new();
+        self.visited(&mut blocks);
+        blocks.into_iter().collect()
+    }
+
+    // Collects the blocks whose visits this expression reads.
+    fn visited<'a>(&'a self, blocks: &mut BTreeSet<&'a str>) {
+        match self {
+            Expr::Visits(block) if !block.is_empty() => {
+                blocks.insert(block);
+            }
+            Expr::Not(expr) | Expr::Neg(expr) => expr.visited(blocks),
+            Expr::Binary(left, _, right) => {
+                left.visited(blocks);
+                right.visited(blocks);
+            }
+            _ => {}
+        }
+    }
+
+    // Returns true if this expression is always a truth value, rather than a number.
+    fn is_truth(&self) -> bool {
+        match self {
+            Expr::Bool(_) | Expr::Not(_) => true,
+            Expr::Binary(_, op, _) => *op == Op::And || *op == Op::Or || op.is_comparison(),
+            _ => false,
+        }
+    }
+
+    // Returns true if this expression is `==` or `!=` with a truth value on either side, so it compares truth values.
+    fn compares_truth(&self) -> bool {
+        match self {
+            Expr::Binary(left, Op::Eq, right) | Expr::Binary(left, Op::Ne, right) => left.is_truth() || right.is_truth(),
+            _ => false,
+        }
+    }
+
+    // Collects the variables this expression reads, as flags if it's read as a truth value and as counters otherwise.
+    fn variables<'a>(&'a self, truth: bool, flags: &mut BTreeSet<&'a str>, counters: &mut BTreeSet<&'a str>) {
+        match self {
+            Expr::Int(_) | Expr::Bool(_) | Expr::Visits(_) => {}
+            Expr::Var(name) if truth => {
+                flags.insert(name);
+            }
+            Expr::Var(name) => {
+                counters.insert(name);
+            }
+            Expr::Not(expr) => expr.variables(true, flags, counters),
+            Expr::Neg(expr) => expr.variables(false, flags, counters),
+            Expr::Binary(left, op, right) => {
+                let truth: bool = *op == Op::And || *op == Op::Or || self.compares_truth();
+                left.variables(truth, flags, counters);
+                right.variables(truth, flags, counters);
+            }
+        }
+    }
+}
+
+impl Op {
+    // Returns true for operators that compare two numbers.
+    fn is_comparison(self) -> bool {
+        matches!(self, Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge)
+    }
+
+    // Returns true for operators that calculate a number from two numbers.
+    fn is_arithmetic(self) -> bool {
+        matches!(self, Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Rem)
+    }
+
+    // Compares two numbers with this operator.
+    fn compare(self, left: i32, right: i32) -> bool {
+        match self {
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+            _ => false,
+        }
+    }
+
+    // Calculates a number from two numbers with this operator, without ever panicking.
+    fn calculate(self, left: i32, right: i32) -> i32 {
+        match self {
+            Op::Add => left.saturating_add(right),
+            Op::Sub => left.saturating_sub(right),
+            Op::Mul => left.saturating_mul(right),
+            Op::Div => left.checked_div(right).unwrap_or(0),
+            Op::Rem => left.checked_rem(right).unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+// Symbols made of two characters, which are read as a single token.
+const PAIRS: &[[char; 2]] = &[['=', '='], ['!', '='], ['<', '='], ['>', '='], ['&', '&'], ['|', '|']];
+
+// Splits an expression into words, numbers, and symbols, along with the byte offset each starts at.
+fn tokenize(text: &str) -> Result<Vec<(usize, String)>, ExprError> {
+    let mut tokens: Vec<(usize, String)> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut token: String = c.to_string();
+        if c.is_alphanumeric() || c == '_' {
+            // Block references like "chapter2.txt#cellar" are read as one word, and only accepted by visits() and visited().
+            while let Some((_, next)) = chars.peek().filter(|(_, next)| next.is_alphanumeric() || "_.#".contains(*next)) {
+                token.push(*next);
+                chars.next();
+            }
+        } else if "=!<>&|".contains(c) {
+            if let Some((_, next)) = chars.peek().filter(|(_, next)| PAIRS.contains(&[c, *next])) {
+                token.push(*next);
+                chars.next();
+            }
+        } else if !"+-*/%()".contains(c) {
+            return Err(ExprError { offset: start, message: format!("unexpected `{}` in condition", c) });
+        }
+
+        tokens.push((start, token));
+    }
+
+    Ok(tokens)
+}
+
+// Parses tokens into an Expr by recursive descent, one method per level of precedence.
+struct Parser {
+    tokens: Vec<(usize, String)>,
+    pos: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<Expr, ExprError> {
+        let mut expr: Expr = self.and()?;
+        while self.eat(&["or", "||"]).is_some() {
+            expr = Expr::Binary(Box::new(expr), Op::Or, Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ExprError> {
+        let mut expr: Expr = self.not()?;
+        while self.eat(&["and", "&&"]).is_some() {
+            expr = Expr::Binary(Box::new(expr), Op::And, Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, ExprError> {
+        if self.eat(&["not", "!"]).is_some() {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ExprError> {
+        let expr: Expr = self.sum()?;
+        let op: Op = match self.eat(&["==", "!=", "<", "<=", ">", ">="]) {
+            Some("==") => Op::Eq,
+            Some("!=") => Op::Ne,
+            Some("<") => Op::Lt,
+            Some("<=") => Op::Le,
+            Some(">") => Op::Gt,
+            Some(">=") => Op::Ge,
+            _ => return Ok(expr),
+        };
+        Ok(Expr::Binary(Box::new(expr), op, Box::new(self.sum()?)))
+    }
+
+    fn sum(&mut self) -> Result<Expr, ExprError> {
+        let mut expr: Expr = self.product()?;
+        while let Some(op) = self.eat(&["+", "-"]) {
+            let op: Op = if op == "+" { Op::Add } else { Op::Sub };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, ExprError> {
+        let mut expr: Expr = self.negation()?;
+        while let Some(op) = self.eat(&["*", "/", "%"]) {
+            let op: Op = match op {
+                "*" => Op::Mul,
+                "/" => Op::Div,
+                _ => Op::Rem,
+            };
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.negation()?));
+        }
+        Ok(expr)
+    }
+
+    fn negation(&mut self) -> Result<Expr, ExprError> {
+        if self.eat(&["-"]).is_some() {
+            Ok(Expr::Neg(Box::new(self.negation()?)))
+        } else {
+            self.atom()
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, ExprError> {
+        let (offset, token) = match self.peek() {
+            Some((offset, token)) => (offset, String::from(token)),
+            None => return Err(self.expected("a number, variable, or `(`")),
+        };
+
+        let first: char = token.chars().next().unwrap_or_default();
+        let expr: Expr = if token == "(" {
+            self.pos += 1;
+            let expr: Expr = self.or()?;
+            if self.eat(&[")"]).is_none() {
+                return Err(self.expected("`)`"));
+            }
+            return Ok(expr);
+        } else if first.is_ascii_digit() {
+            Expr::Int(token.parse().map_err(|_| ExprError { offset, message: format!("`{}` isn't a whole number", token) })?)
+        } else if token == "true" || token == "false" {
+            Expr::Bool(token == "true")
+        } else if token == "first_visit" {
+            Expr::Binary(Box::new(Expr::Visits(String::new())), Op::Eq, Box::new(Expr::Int(1)))
+        } else if (token == "visits" || token == "visited") && self.tokens.get(self.pos + 1).is_some_and(|(_, next)| next == "(") {
+            self.pos += 2;
+            let block: String = match self.peek() {
+                Some((_, block)) if block.starts_with(|c: char| c.is_alphanumeric() || c == '_') => String::from(block),
+                _ => return Err(self.expected("a block name")),
+            };
+            self.pos += 1;
+            if self.eat(&[")"]).is_none() {
+                return Err(self.expected("`)`"));
+            }
+
+            let visits: Expr = Expr::Visits(block);
+            return Ok(if token == "visited" { Expr::Binary(Box::new(visits), Op::Gt, Box::new(Expr::Int(0))) } else { visits });
+        } else if (first.is_alphabetic() || first == '_')
+            && token.chars().all(|c| c.is_alphanumeric() || c == '_')
+            && !["and", "or", "not"].contains(&token.as_str())
+        {
+            Expr::Var(token)
+        } else {
+            return Err(self.expected("a number, variable, or `(`"));
+        };
+
+        self.pos += 1;
+        Ok(expr)
+    }
+
+    // Returns the next token and its offset without consuming it.
+    fn peek(&self) -> Option<(usize, &str)> {
+        self.tokens.get(self.pos).map(|(offset, token)| (*offset, token.as_str()))
+    }
+
+    // Consumes the next token if it's one of the given ones, and returns it.
+    fn eat(&mut self, expected: &[&'static str]) -> Option<&'static str> {
+        let (_, token) = self.peek()?;
+        let found: &'static str = expected.iter().find(|expected| **expected == token)?;
+        self.pos += 1;
+        Some(found)
+    }
+
+    // Describes what was expected at the next token, or at the end of the condition.
+    fn expected(&self, expected: &str) -> ExprError {
+        match self.peek() {
+            Some((offset, token)) => ExprError { offset, message: format!("expected {}, found `{}`", expected, token) },
+            None => ExprError { offset: self.len, message: format!("expected {}, found the end of the condition", expected) },
+        }
+    }
+}
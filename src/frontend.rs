@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::io;
+
+use crate::engine::{Event, Output};
+use crate::write_out::{type_text, Color};
+
+/// Everything the story needs to show text to the player and read their input.
+///
+/// Implement this to play a story anywhere other than the terminal, such as a GUI or a test harness.
+pub trait Frontend {
+    /// Displays a single line of story text in the given color.
+    fn write_line(&mut self, line: &str, color: Color);
+
+    /// Displays an empty line.
+    fn write_break(&mut self);
+
+    /// Presents the text of each option the player may choose from, numbered in the given order.
+    fn present_options(&mut self, options: &[String]);
+
+    /// Asks the player a question, which they will answer with their next input.
+    fn prompt(&mut self, question: &str);
+
+    /// Reads a line of input from the player, or returns None if there is no more input to read.
+    fn read_input(&mut self) -> Option<String>;
+
+    /// Displays every event in the given Output, in order.
+    ///
+    /// ```
+    /// # use intfic::engine::{Event, Output};
+    /// # use intfic::frontend::{Frontend, Mock};
+    /// # use intfic::write_out::Color;
+    /// let mut mock: Mock = Mock::new(&[]);
+    /// mock.show(&Output {
+    ///     events: vec![
+    ///         Event::Line(String::from("Which way?"), Color::Cyan),
+    ///         Event::Options(vec![String::from("Left"), String::from("Right")]),
+    ///     ],
+    /// });
+    ///
+    /// assert_eq!(mock.transcript, vec!["Which way?", "1) Left", "2) Right"]);
+    /// ```
+    fn show(&mut self, output: &Output) {
+        for event in output.events.iter() {
+            match event {
+                Event::Line(line, color) => self.write_line(line, *color),
+                Event::Break => self.write_break(),
+                Event::Options(options) => self.present_options(options),
+                Event::Prompt(question) => self.prompt(question),
+                Event::End => {}
+            }
+        }
+    }
+}
+
+/// Types text out to the terminal with colors and a typewriter effect, and reads input from stdin.
+///
+/// ```no_run
+/// # use intfic::frontend::{Frontend, Terminal};
+/// # use intfic::write_out::Color;
+/// let mut terminal: Terminal = Terminal;
+///
+/// terminal.write_line("\"Hello there!\" She says.", Color::Purple);
+/// let input: Option<String> = terminal.read_input();
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Terminal;
+
+impl Frontend for Terminal {
+    fn write_line(&mut self, line: &str, color: Color) {
+        type_text(line, color, false);
+    }
+
+    fn write_break(&mut self) {
+        println!();
+    }
+
+    fn present_options(&mut self, options: &[String]) {
+        for (num, option) in options.iter().enumerate() {
+            type_text(&format!("{}) {}", num + 1, option), Color::White, true);
+        }
+        println!();
+    }
+
+    fn prompt(&mut self, question: &str) {
+        type_text(question, Color::Cyan, true);
+    }
+
+    fn read_input(&mut self) -> Option<String> {
+        let mut input = String::new();
+
+        match io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(input),
+        }
+    }
+}
+
+/// Plays back a scripted list of inputs and records everything written, so stories can be tested without a terminal.
+///
+/// Options are recorded as "#) text", and empty lines as "".
+///
+/// ```
+/// # use intfic::frontend::{Frontend, Mock};
+/// # use intfic::write_out::Color;
+/// let mut mock: Mock = Mock::new(&["open door"]);
+///
+/// mock.write_line("You see a door.", Color::White);
+/// assert_eq!(mock.read_input(), Some(String::from("open door")));
+/// assert_eq!(mock.read_input(), None);
+/// assert_eq!(mock.transcript, vec!["You see a door."]);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Mock {
+    /// The inputs that have not been read yet.
+    pub inputs: VecDeque<String>,
+    /// Every line written so far, in order.
+    pub transcript: Vec<String>,
+}
+
+impl Mock {
+    /// Creates a Mock frontend that will answer with the given inputs, in order.
+    ///
+    /// ```
+    /// # use intfic::frontend::Mock;
+    /// let mock: Mock = Mock::new(&["yes", "north"]);
+    ///
+    /// assert_eq!(mock.inputs.len(), 2);
+    /// assert!(mock.transcript.is_empty());
+    /// ```
+    pub fn new(inputs: &[&str]) -> Mock {
+        Mock {
+            inputs: inputs.iter().map(|input| String::from(*input)).collect(),
+            transcript: Vec::new(),
+        }
+    }
+}
+
+impl Frontend for Mock {
+    fn write_line(&mut self, line: &str, _color: Color) {
+        self.transcript.push(String::from(line));
+    }
+
+    fn write_break(&mut self) {
+        self.transcript.push(String::default());
+    }
+
+    fn present_options(&mut self, options: &[String]) {
+        for (num, option) in options.iter().enumerate() {
+            self.transcript.push(format!("{}) {}", num + 1, option));
+        }
+    }
+
+    fn prompt(&mut self, question: &str) {
+        self.transcript.push(String::from(question));
+    }
+
+    fn read_input(&mut self) -> Option<String> {
+        self.inputs.pop_front()
+    }
+}
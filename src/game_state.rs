@@ -1,22 +1,26 @@
-use dirs::data_local_dir;
-use ron::de::from_reader;
-use ron::ser::{to_string_pretty, PrettyConfig};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
-use std::fs::{create_dir_all, remove_file, File};
-use std::io::prelude::*;
-use std::process;
+use std::mem;
+use std::path::PathBuf;
+use std::time::Instant;
 
-use crate::parse_file::load_file;
-use crate::story_block::start_block;
+use crate::engine::Engine;
+use crate::frontend::Frontend;
+use crate::manifest::{Manifest, Variable};
+use crate::save::{SaveError, SlotInfo, QUICKSAVE};
+use crate::store::{FileStore, Format, SaveStore};
+use crate::story::split_target;
 use crate::write_out::{type_text, Color};
 use crate::DEBUG;
 
-/// GameState holds information about the name of the game, story progress, boolean flags, and integer counters.
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+// The most blocks kept as checkpoints.
+const CHECKPOINTS: usize = 10;
+
+/// GameState holds information about the name of the game, story progress, boolean flags, integer counters, and strings.
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct GameState {
-    /// The name of your game, also used as the name for its save file.
+    /// The name of your game, also used as the name of the directory its save slots are kept in.
     pub name: String,
 
     /// A tuple representing the filename of the story file and the name of the story block curently presented.
@@ -27,6 +31,68 @@ pub struct GameState {
 
     /// A HashMap environment of named integers that can be modified and checked against at runtime.
     pub counters: HashMap<String, i32>,
+
+    /// A HashMap environment of named strings, such as the player's name, that can be set by the player at runtime.
+    #[serde(default)]
+    pub strings: HashMap<String, String>,
+
+    /// The lowest and highest values each named counter may take, counters without limits are unbounded.
+    #[serde(default)]
+    pub limits: HashMap<String, (i32, i32)>,
+
+    /// How many times each block has been read, by references like "example_1.txt#start".
+    #[serde(default)]
+    pub visits: HashMap<String, u32>,
+
+    /// How many times each sequence has been read, by references like "example_1.txt#start~0" for the first sequence in a block.
+    #[serde(default)]
+    pub sequences: HashMap<String, u32>,
+
+    /// How many times each option has been chosen, by references like "example_1.txt#start*0" for the first option in a block.
+    #[serde(default)]
+    pub chosen: HashMap<String, u32>,
+
+    /// How long the game was played for before this GameState was created or loaded, in seconds.
+    #[serde(default)]
+    pub played: u64,
+
+    /// The last few blocks entered, oldest first, by references like "example_1.txt#start".
+    /// If a loaded save's block is no longer in the story, the [Engine](../engine/struct.Engine.html) falls back to the most recent of these that is.
+    #[serde(default)]
+    pub checkpoints: Vec<String>,
+
+    /// A random number picked when the game is created, which shuffled sequences are shuffled with,
+    /// so each playthrough sees them in a different order. Saves from before it was added have a seed of 0.
+    #[serde(default)]
+    pub seed: u64,
+
+    /// A fingerprint of the story being played, stored with saves to tell when the story has changed since they were made.
+    /// Set by the [Engine](../engine/struct.Engine.html), and 0 if it isn't known. See [Loader::fingerprint()](../parse_file/struct.Loader.html#method.fingerprint)
+    #[serde(skip)]
+    pub story_hash: u64,
+
+    /// The description of the save this GameState was just loaded from, until the [Engine](../engine/struct.Engine.html)
+    /// has checked it against the story being played.
+    #[serde(skip)]
+    pub loaded: Option<SlotInfo>,
+
+    /// Set when the player asks to quit with the "exit" keyword. The [Engine](../engine/struct.Engine.html) ends the story
+    /// when it's set, and it's up to the program hosting the game whether to exit.
+    #[serde(skip)]
+    pub quitting: bool,
+
+    /// The last line of story text shown to the player, kept up to date by the [Engine](../engine/struct.Engine.html)
+    /// and stored with saves so they can be told apart.
+    #[serde(skip)]
+    pub excerpt: String,
+
+    // When this GameState was created or loaded, to tell how long it has been played for.
+    #[serde(skip)]
+    session: Option<Instant>,
+
+    // Where save slots are kept, set with set_store(). None keeps them in the default FileStore.
+    #[serde(skip)]
+    store: Option<Box<dyn SaveStore>>,
 }
 
 impl GameState {
@@ -48,9 +114,64 @@ impl GameState {
             progress: (String::default(), String::default()),
             flags: HashMap::new(),
             counters: counters_init,
+            strings: HashMap::new(),
+            limits: HashMap::new(),
+            visits: HashMap::new(),
+            sequences: HashMap::new(),
+            chosen: HashMap::new(),
+            played: 0,
+            checkpoints: Vec::new(),
+            seed: rand::random(),
+            story_hash: 0,
+            loaded: None,
+            quitting: false,
+            excerpt: String::new(),
+            session: Some(Instant::now()),
+            store: None,
         }
     }
 
+    /// Creates a new GameState from a story's Manifest, named after its title and starting where it starts.
+    /// 
+    /// The Manifest's flags, counters, and strings are set on top of the "score" counter every GameState starts with,
+    /// followed by the defaults and limits of its declared variables.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::manifest::Manifest;
+    /// let manifest: Manifest = Manifest::parse("(
+    ///     title: \"Test Story\",
+    ///     start: (\"example_1.txt\", \"start\"),
+    ///     counters: { \"strength\": 10 },
+    /// )").unwrap();
+    /// let game: GameState = GameState::from_manifest(&manifest);
+    /// 
+    /// assert_eq!(game.name, String::from("Test Story"));
+    /// assert_eq!(game.progress, (String::from("example_1.txt"), String::from("start")));
+    /// assert_eq!(game.get_counter("strength"), 10);
+    /// assert_eq!(game.get_counter("score"), 0);
+    /// ```
+    pub fn from_manifest(manifest: &Manifest) -> GameState {
+        let mut game: GameState = GameState::new(&manifest.title);
+
+        game.set_progress(&manifest.start.0, &manifest.start.1);
+        game.flags.extend(manifest.flags.clone());
+        game.counters.extend(manifest.counters.clone());
+        game.strings.extend(manifest.strings.clone());
+
+        for (name, variable) in manifest.variables.iter() {
+            match variable {
+                Variable::Flag { default } => game.set_flag(name, *default),
+                Variable::Text { default } => game.set_string(name, default),
+                Variable::Counter { default, min, max } => {
+                    game.set_counter(name, *default);
+                    game.limit_counter(name, *min, *max);
+                }
+            }
+        }
+        game
+    }
+
     /// If the given flag is in our GameState flags Hashmap, return it. Otherwise return false.
     /// 
     /// ```
@@ -93,6 +214,36 @@ impl GameState {
         }
     }
 
+    /// If the given string is in our GameState strings HashMap, return it. Otherwise, return an empty string.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// assert_eq!(game.get_string("not_set_string"), "");
+    /// 
+    /// game.set_string("player_name", "Sam");
+    /// assert_eq!(game.get_string("player_name"), "Sam");
+    /// ```
+    pub fn get_string(&self, name: &str) -> &str {
+        self.strings.get(name).map_or("", String::as_str)
+    }
+
+    /// Sets or replaces a string in the GameState strings HashMap.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.set_string("companion", "Rex");
+    /// assert_eq!(game.strings[&String::from("companion")], String::from("Rex"));
+    /// game.set_string("companion", "Fido");
+    /// assert_eq!(game.strings[&String::from("companion")], String::from("Fido"));
+    /// ```
+    pub fn set_string(&mut self, name: &str, val: &str) {
+        self.strings.insert(String::from(name), String::from(val));
+    }
+
     /// Sets or updates a flag in the GameState flags HashMap.
     /// 
     /// ```
@@ -108,7 +259,44 @@ impl GameState {
         self.flags.insert(String::from(name), val);
     }
 
-    /// Sets or adds to a counter in the GameState flags HashMap.
+    /// Sets a counter in the GameState counters HashMap, keeping it within any limits set for it.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.set_counter("test_counter", 50);
+    /// assert_eq!(game.get_counter("test_counter"), 50);
+    /// game.limit_counter("test_counter", 0, 10);
+    /// game.set_counter("test_counter", -5);
+    /// assert_eq!(game.get_counter("test_counter"), 0);
+    /// ```
+    pub fn set_counter(&mut self, name: &str, val: i32) {
+        let new_val: i32 = match self.limits.get(name) {
+            Some((min, max)) => val.max(*min).min(*max),
+            None => val,
+        };
+        self.counters.insert(String::from(name), new_val);
+    }
+
+    /// Keeps a counter between the given lowest and highest values from now on, clamping its current value if needed.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.set_counter("health", 15);
+    /// game.limit_counter("health", 0, 10);
+    /// assert_eq!(game.get_counter("health"), 10);
+    /// game.update_counter("health", -25);
+    /// assert_eq!(game.get_counter("health"), 0);
+    /// ```
+    pub fn limit_counter(&mut self, name: &str, min: i32, max: i32) {
+        self.limits.insert(String::from(name), (min, max.max(min)));
+        self.set_counter(name, self.get_counter(name));
+    }
+
+    /// Adds to a counter in the GameState counters HashMap, keeping it within any limits set for it.
     /// 
     /// ```
     /// # use intfic::game_state::GameState;
@@ -120,8 +308,7 @@ impl GameState {
     /// assert_eq!(game.counters[&String::from("test_counter")], 0);
     /// ```
     pub fn update_counter(&mut self, name: &str, val: i32) {
-        let new_val: i32 = self.get_counter(name) + val;
-        self.counters.insert(String::from(name), new_val);
+        self.set_counter(name, self.get_counter(name).saturating_add(val));
     }
 
     /// Helper to add the given i32 to the score counter.
@@ -153,124 +340,381 @@ impl GameState {
         self.progress.1 = String::from(block);
     }
 
-    /// Saves the game to "\<local data dir>\rust_intfic\\\<game name>.ron".
+    /// Returns the progress in our GameState as a reference like "example_1.txt#start".
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.set_progress("example_1.txt", "start");
+    /// assert_eq!(game.progress_ref(), String::from("example_1.txt#start"));
+    /// game.set_progress("example_1.txt", "");
+    /// assert_eq!(game.progress_ref(), String::from("example_1.txt"));
+    /// ```
+    pub fn progress_ref(&self) -> String {
+        if self.progress.1.is_empty() {
+            self.progress.0.clone()
+        } else {
+            format!("{}#{}", self.progress.0, self.progress.1)
+        }
+    }
+
+    /// Helper to set the progress in our GameState from a reference like "example_1.txt#start".
+    /// 
+    /// A reference without a block, like "example_1.txt", starts from the first block of the file.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.set_progress_ref("example_2.txt#lose_computer");
+    /// assert_eq!(game.progress, (String::from("example_2.txt"), String::from("lose_computer")));
+    /// game.set_progress_ref("example_1.txt");
+    /// assert_eq!(game.progress, (String::from("example_1.txt"), String::default()));
+    /// ```
+    pub fn set_progress_ref(&mut self, reference: &str) {
+        match split_target(reference) {
+            Some((story, block)) => self.set_progress(story, block),
+            None => self.set_progress(reference, ""),
+        }
+    }
+
+    /// Records that the given block of the given story file has been read once more, and makes it the latest checkpoint.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.visit("example_1.txt", "start");
+    /// game.visit("example_1.txt", "neat");
+    /// game.visit("example_1.txt", "start");
+    /// assert_eq!(game.visits.get("example_1.txt#start"), Some(&2));
+    /// assert_eq!(game.checkpoints, vec!["example_1.txt#neat", "example_1.txt#start"]);
+    /// ```
+    pub fn visit(&mut self, story: &str, block: &str) {
+        let reference: String = format!("{}#{}", story, block);
+        let count: &mut u32 = self.visits.entry(reference.clone()).or_insert(0);
+        *count = count.saturating_add(1);
+
+        self.checkpoints.retain(|checkpoint| *checkpoint != reference);
+        if self.checkpoints.len() == CHECKPOINTS {
+            self.checkpoints.remove(0);
+        }
+        self.checkpoints.push(reference);
+    }
+
+    /// Returns how many times a block has been read, given a reference like "example_1.txt#start" or the name of a block in the current story file.
     /// 
-    /// * On Windows, \<local data dir> corresponds to "C:\Users\<username>\AppData\Local".
-    /// * On macOS, \<local data dir> corresponds to "/Users/\<username>/Library/Application Support".
-    /// * On Linux, \<local data dir> corresponds to "/home/\<username>/.local/share".
+    /// An empty reference is the block currently presented.
     /// 
-    /// Saving overwrites any previous save file currently.
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("example_1.txt", "start");
+    /// game.visit("example_1.txt", "start");
+    /// 
+    /// assert_eq!(game.get_visits("start"), 1);
+    /// assert_eq!(game.get_visits("example_1.txt#start"), 1);
+    /// assert_eq!(game.get_visits(""), 1);
+    /// assert_eq!(game.get_visits("neat"), 0);
+    /// ```
+    pub fn get_visits(&self, reference: &str) -> u32 {
+        let key: String = match split_target(reference) {
+            Some((story, block)) => format!("{}#{}", story, block),
+            None if reference.is_empty() => format!("{}#{}", self.progress.0, self.progress.1),
+            None => format!("{}#{}", self.progress.0, reference),
+        };
+        self.visits.get(&key).copied().unwrap_or(0)
+    }
+
+    /// Records that the given sequence has been read once more, returning how many times it had been read before.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// assert_eq!(game.advance_sequence("example_1.txt#start~0"), 0);
+    /// assert_eq!(game.advance_sequence("example_1.txt#start~0"), 1);
+    /// assert_eq!(game.sequences.get("example_1.txt#start~0"), Some(&2));
+    /// ```
+    pub fn advance_sequence(&mut self, reference: &str) -> u32 {
+        let count: &mut u32 = self.sequences.entry(String::from(reference)).or_insert(0);
+        let before: u32 = *count;
+        *count = count.saturating_add(1);
+        before
+    }
+
+    /// Records that the given option has been chosen once more.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.choose("example_1.txt#start*0");
+    /// assert_eq!(game.chosen.get("example_1.txt#start*0"), Some(&1));
+    /// ```
+    pub fn choose(&mut self, reference: &str) {
+        let count: &mut u32 = self.chosen.entry(String::from(reference)).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+
+    /// Returns how many times the given option has been chosen, given a reference like "example_1.txt#start*0".
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// assert_eq!(game.times_chosen("example_1.txt#start*0"), 0);
+    /// game.choose("example_1.txt#start*0");
+    /// assert_eq!(game.times_chosen("example_1.txt#start*0"), 1);
+    /// ```
+    pub fn times_chosen(&self, reference: &str) -> u32 {
+        self.chosen.get(reference).copied().unwrap_or(0)
+    }
+
+    /// Returns how long the game has been played for in seconds, including earlier sessions that were saved.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.played = 90;
+    /// 
+    /// assert!(game.playtime() >= 90);
+    /// ```
+    pub fn playtime(&self) -> u64 {
+        let session: u64 = self.session.map_or(0, |start| start.elapsed().as_secs());
+        self.played.saturating_add(session)
+    }
+
+    /// Saves the game to the quicksave slot. See [save_slot()](#method.save_slot).
+    /// 
+    /// ```no_run
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.save().unwrap();
+    /// assert_eq!(game.get_flag("saved"), true);
+    /// ```
+    pub fn save(&mut self) -> Result<(), SaveError> {
+        self.save_slot(QUICKSAVE)
+    }
+
+    /// Loads the game from the quicksave slot. See [load_slot()](#method.load_slot).
+    /// 
+    /// ```no_run
+    /// # use intfic::game_state::GameState;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.save().unwrap();
+    /// game.set_progress("example_1.txt", "start");
+    /// game.load().unwrap();
+    /// assert_eq!(game.progress, (String::default(), String::default()));
+    /// ```
+    pub fn load(&mut self) -> Result<(), SaveError> {
+        self.load_slot(QUICKSAVE)
+    }
+
+    /// Saves the game to the given slot of our [SaveStore](../store/trait.SaveStore.html), set with [set_store()](#method.set_store).
+    /// 
+    /// Unless another has been set, saves are kept in RON files in the local data directory, "\<local data dir>/rust_intfic/\<game name>/\<slot>.ron".
+    /// See [FileStore](../store/struct.FileStore.html) for where that is.
+    /// 
+    /// Saving overwrites any previous save in the same slot.
+    /// The save is stored along with a [SlotInfo](../save/struct.SlotInfo.html) describing it, so slots can be listed without loading them.
     /// 
     /// Saving sets a flag in our GameState to indicate it is safe to quit.
+    /// If the save can't be written, a [SaveError](../save/enum.SaveError.html) describing why is returned instead, and the flag is left as it was.
     /// 
     /// ```no_run
     /// # use intfic::game_state::GameState;
     /// let mut game: GameState = GameState::new("Test GameState");
     /// 
-    /// game.save();
-    /// assert_eq!(game.get_flag("game_saved"), true);
+    /// match game.save_slot("before the dragon") {
+    ///     Ok(()) => println!("Game Saved!"),
+    ///     Err(e) => println!("{}", e),
+    /// }
     /// ```
-    /// "Test GameState.ron":
+    /// "Test GameState/before the dragon.ron":
     /// ```ron
-    /// (
+    /// ((
+    ///     slot: "before the dragon",
+    ///     saved_at: 1792166400,
+    ///     block: "",
+    ///     excerpt: "",
+    ///     playtime: 0,
+    ///     version: 2,
+    ///     story_hash: 0,
+    /// ), (
     ///     name: "Test GameState",
     ///     progress: ("", ""),
-    ///     flags: {},
+    ///     flags: {
+    ///         "saved": true,
+    ///     },
     ///     counters: {
     ///        "score": 0,
     ///     },
-    /// )
+    ///     strings: {},
+    ///     limits: {},
+    ///     visits: {},
+    ///     sequences: {},
+    ///     chosen: {},
+    ///     played: 0,
+    ///     checkpoints: [],
+    /// ))
     /// ```
-    pub fn save(&mut self) {
-        let save_string =
-            to_string_pretty(self, PrettyConfig::new()).expect("Serialization failed");
+    pub fn save_slot(&mut self, slot: &str) -> Result<(), SaveError> {
+        let was_saved: bool = self.get_flag("saved");
+        self.set_flag("saved", true);
+        self.played = self.playtime();
+        self.session = Some(Instant::now());
+
+        let info: SlotInfo = SlotInfo::new(slot, self);
+        let mut store: Box<dyn SaveStore> = self.take_store();
+        let written = store.write(&self.name, &info, self);
+        self.store = Some(store);
 
-        if let Some(local_data_dir) = data_local_dir() {
-            let save_dir = local_data_dir.join("rust_intfic\\");
-            create_dir_all(&save_dir).expect("Couldn't create save directory");
+        if written.is_err() {
+            self.set_flag("saved", was_saved);
+        }
+        written
+    }
 
-            let save_path = save_dir.join(format!("{}.ron", self.name));
-            let display = save_path.display();
+    /// Loads the game from the given slot of our [SaveStore](../store/trait.SaveStore.html).
+    /// 
+    /// If the load is successful, the current GameState will be overwritten with the loaded one, though it keeps our SaveStore.
+    /// Saves written in older versions of the save format are upgraded as they're loaded, see [SAVE_VERSION](../save/constant.SAVE_VERSION.html).
+    /// The next time the [Engine](../engine/struct.Engine.html) starts or resumes, it checks the save against its story,
+    /// migrating saves made with other versions of the story and falling back to a checkpoint if the save's block is gone.
+    /// If the slot is empty, or its save can't be read, a [SaveError](../save/enum.SaveError.html) describing why is returned
+    /// and the current GameState is left as it was.
+    /// 
+    /// ```no_run
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::SaveError;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// game.save_slot("2").unwrap();
+    /// game.set_progress("example_1.txt", "start");
+    /// game.load_slot("2").unwrap();
+    /// assert_eq!(game.progress, (String::default(), String::default()));
+    /// assert_eq!(game.load_slot("empty"), Err(SaveError::Missing { slot: String::from("empty") }));
+    /// ```
+    pub fn load_slot(&mut self, slot: &str) -> Result<(), SaveError> {
+        let (info, game): (SlotInfo, GameState) = match &self.store {
+            Some(store) => store.read(&self.name, slot)?,
+            None => FileStore::default().read(&self.name, slot)?,
+        };
 
-            if save_path.exists() {
-                remove_file(&save_path).expect("Couldn't remove old save file");
-            }
+        let store: Option<Box<dyn SaveStore>> = self.store.take();
+        let story_hash: u64 = self.story_hash;
+        *self = game;
+        self.session = Some(Instant::now());
+        self.store = store;
+        self.story_hash = story_hash;
+        self.excerpt = info.excerpt.clone();
+        self.loaded = Some(info);
+        Ok(())
+    }
 
-            let mut save_file = match File::create(&save_path) {
-                Err(e) => panic!("couldn't create {}: {}", display, e),
-                Ok(file) => file,
-            };
+    /// Deletes the save in the given slot of our [SaveStore](../store/trait.SaveStore.html),
+    /// returning a [SaveError](../save/enum.SaveError.html) if there isn't one or it can't be removed.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::store::MemoryStore;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_store(MemoryStore::new());
+    /// 
+    /// game.save_slot("3").unwrap();
+    /// game.delete_slot("3").unwrap();
+    /// assert!(game.slots().unwrap().iter().all(|info| info.slot != "3"));
+    /// ```
+    pub fn delete_slot(&mut self, slot: &str) -> Result<(), SaveError> {
+        let mut store: Box<dyn SaveStore> = self.take_store();
+        let deleted = store.delete(&self.name, slot);
+        self.store = Some(store);
+        deleted
+    }
 
-            match save_file.write_all(save_string.as_bytes()) {
-                Err(e) => panic!("couldn't write to {}: {}", display, e),
-                Ok(_) => {
-                    type_text("Game Saved!", Color::White, false);
-                    self.set_flag("saved", true);
-                }
-            }
-        } else {
-            type_text("Error accessing local appdata", Color::Red, false);
-        }
+    /// Returns descriptions of the slots this game has been saved to in our [SaveStore](../store/trait.SaveStore.html), most recently saved first.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::store::MemoryStore;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_store(MemoryStore::new());
+    /// 
+    /// game.save_slot("1").unwrap();
+    /// for info in game.slots().unwrap() {
+    ///     println!("{}", info);
+    /// }
+    /// ```
+    pub fn slots(&self) -> Result<Vec<SlotInfo>, SaveError> {
+        let mut slots: Vec<SlotInfo> = match &self.store {
+            Some(store) => store.list(&self.name)?,
+            None => FileStore::default().list(&self.name)?,
+        };
+
+        slots.sort_by(|a, b| b.saved_at.cmp(&a.saved_at).then_with(|| a.slot.cmp(&b.slot)));
+        Ok(slots)
     }
 
-    /// Loads the game from "\<local data dir>\rust_intfic\\\<game name>.ron".
+    /// Keeps this game's saves in the given [SaveStore](../store/trait.SaveStore.html), such as a
+    /// [MemoryStore](../store/struct.MemoryStore.html) that never touches the disk, or a [FileStore](../store/struct.FileStore.html)
+    /// writing JSON or binary saves instead of RON.
     /// 
-    /// * On Windows, \<local data dir> corresponds to "C:\Users\<username>\AppData\Local".
-    /// * On macOS, \<local data dir> corresponds to "/Users/\<username>/Library/Application Support".
-    /// * On Linux, \<local data dir> corresponds to "/home/\<username>/.local/share".
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::store::{MemoryStore, SaveStore};
+    /// let store: MemoryStore = MemoryStore::new();
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_store(store.clone());
+    /// 
+    /// game.save().unwrap();
+    /// assert_eq!(store.list("Test GameState").unwrap().len(), 1);
+    /// ```
+    pub fn set_store<S: SaveStore + 'static>(&mut self, store: S) {
+        self.store = Some(Box::new(store));
+    }
+
+    /// Keeps this game's saves in RON files under the given directory instead of the local data directory, such as beside a portable install.
     /// 
-    /// If the load is successful, the current GameState will be overwritten with the loaded one.
+    /// This is the same as setting a [FileStore](../store/struct.FileStore.html) with that directory as its root,
+    /// and is how "--save-dir" works when playing a story with "cargo run".
     /// 
     /// ```no_run
     /// # use intfic::game_state::GameState;
     /// let mut game: GameState = GameState::new("Test GameState");
     /// 
-    /// game.save();
-    /// game.set_progress("example_1.txt", "start");
-    /// game.load();
-    /// assert_eq!(game.progress, (String::default(), String::default()));
+    /// game.set_save_dir("saves");
+    /// game.save().unwrap(); // writes "saves/Test GameState/quicksave.ron"
     /// ```
-    pub fn load(&mut self) {
-        if let Some(local_data_dir) = data_local_dir() {
-            let save_dir = local_data_dir.join(format!("rust_intfic\\{}.ron", self.name));
-            let display = save_dir.display();
-
-            if save_dir.exists() {
-                let save_file = match File::open(&save_dir) {
-                    Err(e) => panic!("couldn't load from {}: {}", display, e),
-                    Ok(file) => file,
-                };
-
-                match from_reader(save_file) {
-                    Ok(new_state) => {
-                        *self = new_state;
-                        type_text("Game Loaded!", Color::White, false);
-                    }
-                    Err(e) => panic!("Couldn't deserialize gamestate from {}: {}", display, e),
-                };
-            } else {
-                type_text("No save data found", Color::Red, false);
-            }
-        } else {
-            type_text("Error accessing local appdata", Color::Red, false);
-        }
+    pub fn set_save_dir<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.set_store(FileStore::in_dir(dir, Format::Ron));
     }
 
-    /// Searhes for the story file and block indicated in "progress", then starts reading the story there if successful.
+    // Takes our SaveStore out of this GameState, so it can be given the GameState while it's used.
+    fn take_store(&mut self) -> Box<dyn SaveStore> {
+        self.store.take().unwrap_or_else(|| Box::new(FileStore::default()))
+    }
+
+    /// Searches for the story file and block indicated in "progress", then plays the story from there through the given Frontend.
+    /// 
+    /// The story is driven by an [Engine](../engine/struct.Engine.html), reading input from the player whenever they have a choice to make.
+    /// Once the story ends, this GameState is updated with everything that happened along the way.
     /// 
     /// ```no_run
+    /// # use intfic::frontend::Terminal;
     /// # use intfic::game_state::GameState;
     /// let mut game: GameState = GameState::new("Test GameState");
     /// 
     /// game.set_progress("example_1.txt", "start");
-    /// game.start();
+    /// game.start(&mut Terminal);
     /// ```
-    pub fn start(&mut self) {
-        if let Some(loaded_blocks) = load_file(&(self.progress.0.clone()[..]), self) {
-            start_block(self.progress.1.clone(), &loaded_blocks, self);
-        } else {
-            panic!("Couldn't start story: {}", self.progress.0.clone());
-        }
+    pub fn start(&mut self, frontend: &mut dyn Frontend) {
+        let mut engine: Engine = Engine::new(mem::take(self));
+        engine.run(frontend);
+        *self = engine.game;
     }
 
     /// Helper to check if a "game_over" flag is true in our GameState flags Hashmap, and quit the game if so.
@@ -282,25 +726,28 @@ impl GameState {
     /// game.check_game_over(); // will do nothing
     /// game.set_flag("game_over", true);
     /// game.check_game_over(); // will call self.quit()!
+    /// assert!(game.quitting);
     /// ```
-    pub fn check_game_over(&self) {
+    pub fn check_game_over(&mut self) {
         if self.get_flag("game_over") {
             self.quit();
         }
     }
 
-    /// Prints out the current GameState and then stops execution.
+    /// Says goodbye to the player and sets [quitting](#structfield.quitting), the same as the "exit" keyword.
+    /// 
+    /// The program keeps running, so it's up to the program hosting the game to stop asking for input once it's set.
     /// 
     /// ```no_run
     /// # use intfic::game_state::GameState;
     /// let mut game: GameState = GameState::new("Test GameState");
     /// 
-    /// game.quit(); // will call self.print_debug(), then stop execution.
+    /// game.quit();
+    /// assert!(game.quitting);
     /// ```
-    pub fn quit(&self) {
+    pub fn quit(&mut self) {
         type_text("See you next time!", Color::White, false);
-        self.print_debug();
-        process::exit(0);
+        self.quitting = true;
     }
 
     /// Prints the current state of the game if DEBUG is enabled.
@@ -316,6 +763,7 @@ impl GameState {
     ///   Progress: [Story: {}, Block: {}]
     ///   Flags: {:?}
     ///   Counters: {:?}
+    ///   Strings: {:?}
     /// */
     /// ```
     pub fn print_debug(&self) {
@@ -325,12 +773,35 @@ impl GameState {
     }
 }
 
+// GameStates are equal if they've reached the same point of the same story in the same way,
+// no matter when they were created or loaded, or where they're saved.
+impl PartialEq for GameState {
+    fn eq(&self, other: &GameState) -> bool {
+        self.name == other.name
+            && self.progress == other.progress
+            && self.flags == other.flags
+            && self.counters == other.counters
+            && self.strings == other.strings
+            && self.limits == other.limits
+            && self.visits == other.visits
+            && self.sequences == other.sequences
+            && self.chosen == other.chosen
+            && self.played == other.played
+            && self.checkpoints == other.checkpoints
+            && self.seed == other.seed
+            && self.excerpt == other.excerpt
+            && self.story_hash == other.story_hash
+            && self.loaded == other.loaded
+            && self.quitting == other.quitting
+    }
+}
+
 impl fmt::Display for GameState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "  Name: {}\n  Progress: [Story: {}, Block: {}]\n  Flags: {:?}\n  Counters: {:?}\n",
-            self.name, self.progress.0, self.progress.1, self.flags, self.counters,
+            "  Name: {}\n  Progress: [Story: {}, Block: {}]\n  Flags: {:?}\n  Counters: {:?}\n  Strings: {:?}\n  Visits: {:?}\n  Sequences: {:?}\n  Chosen: {:?}\n",
+            self.name, self.progress.0, self.progress.1, self.flags, self.counters, self.strings, self.visits, self.sequences, self.chosen,
         )
     }
 }
@@ -9,8 +9,14 @@
 //! 
 //! * Write text or specific quotes of text with different colors
 //! * Display options that trigger different Story Blocks or Story Files
-//! * Set flags or add to counters in the GameState
-//! * Check flags or counters in the GameState and conditionally display text or options
+//! * Set flags or add to, subtract from, multiply, or set counters in the GameState
+//! * Check conditions on flags and counters in the GameState and conditionally display text, options, or whole sections of a block
+//! * Show the current values of flags, counters, and strings in text and options with placeholders like `{player_name}`
+//! * Vary the text of blocks read more than once with stopping, cycling, shuffled, or once-only sequences
+//! * Mark options to disappear once chosen, or to appear only when nothing else is available
+//! * Save to as many named slots as you like, each showing where the player was, what they last read, and how long they've played
+//! * Keep old saves working as your story changes, with migrations that rename blocks, flags, and counters
+//! * Keep saves as RON, JSON, or compact binary files, in memory, or anywhere else you implement a SaveStore for
 //! 
 //! Additionally, I've included some basic functions for asking yes-no questions and traveling in the cardinal directions, should you prefer to take a more "text adventure" approach with code.
 //! 
@@ -20,7 +26,8 @@
 //! 
 //! 1. Run the example with "cargo run"
 //! 2. Examine the example story files and read up on the [intfic Story File Markup Specification](parse_file/index.html#story-file-markup-specification)
-//! 3. Write you own story, and update main.rs to start it!
+//! 3. Write you own story, list its files and starting point in a [story.ron manifest](manifest/struct.Manifest.html), and play it with "cargo run -- \<story dir>"
+//! 4. Check your story for broken links and other mistakes with "cargo run -- check \<story file>"
 //! 
 //! ## License
 //! 
@@ -28,18 +35,51 @@
 
 use std::time;
 
+/// Bundles story files into the binary at compile time, so a game can ship as a single executable.
+pub mod embed;
+
+/// Steps through a story one input at a time, producing events for the host program to display.
+pub mod engine;
+
+/// Parses and evaluates the conditions written in story files, such as `has_key and strength > 10`.
+pub mod expr;
+
+/// Displays story output and reads player input, whether in the terminal or somewhere else entirely.
+pub mod frontend;
+
 /// Stores, saves, and loads an environment that can be changed and referenced by your story.
 pub mod game_state;
 
+/// Checks a whole story for broken links, unreachable blocks, and other likely mistakes.
+pub mod lint;
+
+/// Describes a story's title, starting point, and story files, so a story directory can be played without writing code.
+pub mod manifest;
+
 /// Parses story files and constructs a list of StoryBlock's.
 pub mod parse_file;
 
 /// Sanitizes and parses input, checking for system keywords.
 pub mod parse_input;
 
+/// Describes the save slots a game can be saved to, so players can keep more than one save.
+pub mod save;
+
+/// Keeps saves in files or in memory, in RON, JSON, or a compact binary format, or anywhere else that implements SaveStore.
+pub mod store;
+
+/// Holds every story file of a story, parsed once and indexed by file and block name.
+pub mod story;
+
 /// Represents an atomic chunk of story with text, effects, and options.
 pub mod story_block;
 
+/// Fills in `{name}` placeholders in story text with the current values of variables in the GameState.
+pub mod template;
+
+/// Small helpers shared between modules, such as hashing that gives the same result everywhere.
+mod util;
+
 /// Writes text with a typewriter effect and a variety of possible colors.
 pub mod write_out;
 
@@ -58,6 +98,33 @@ pub const LINETIME: time::Duration = time::Duration::from_millis(1200);
 /// The base amount of time before the next charater of a line is typed, if FASTMODE is disabled.
 pub const TYPETIME: time::Duration = time::Duration::from_millis(24);
 
+/// Creates a [Loader](parse_file/struct.Loader.html) for story files embedded in the binary at compile time.
+/// 
+/// Given just a directory, this includes the bundle written for that directory by [embed::bundle_story()](embed/fn.bundle_story.html)
+/// in your build script, so the directory is relative to your crate root and every story file in it is embedded.
+/// 
+/// Given a directory and a list of story files, those files are embedded directly without a build script.
+/// In this case the directory is relative to the source file the macro is used in, like `include_str!`.
+/// 
+/// ```
+/// # use intfic::include_story;
+/// # use intfic::parse_file::Loader;
+/// let loader: Loader = include_story!("../resources", ["example_1.txt", "example_2.txt"]);
+/// 
+/// assert_eq!(loader.load("example_2.txt").unwrap()[0].name, String::from("start"));
+/// ```
+#[macro_export]
+macro_rules! include_story {
+    ($dir:literal) => {
+        $crate::parse_file::Loader::from_files(
+            include!(env!(concat!("INTFIC_STORY_", $dir))).iter().copied()
+        )
+    };
+    ($dir:literal, [$($file:literal),+ $(,)?]) => {
+        $crate::parse_file::Loader::from_files(vec![$(($file, include_str!(concat!($dir, "/", $file)))),+])
+    };
+}
+
 /// Prints a string if DEBUG is enabled.
 pub fn print_debug(to_print: String) {
     if DEBUG {
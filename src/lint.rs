@@ -0,0 +1,521 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::expr::Expr;
+use crate::game_state::GameState;
+use crate::parse_file::{Loader, ParseError};
+use crate::story::{split_target, targets};
+use crate::story_block::{ChoiceKind, Statement, StoryBlock};
+use crate::template;
+
+// Line prefixes the parser understands, everything else is text.
+const PREFIXES: &[&str] = &[":-", "=-", "+-", "$-", "~-", "*-", "->", "?-", "#-"];
+
+// Words that open, continue, or close conditional sections.
+const SECTIONS: &[&str] = &["if-", "elif-", "else-", "endif-", "switch-", "case-", "default-", "endswitch-"];
+
+// Color prefixes the text reader understands.
+const COLORS: &[&str] = &["-b ", "-c ", "-g ", "-p ", "-r ", "-y "];
+
+// Flags the engine sets on its own, which stories may check without setting.
+const SYSTEM_FLAGS: &[&str] = &["saved"];
+
+/// How serious a Problem is. Errors break the story, while warnings are likely mistakes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The story can't be played correctly.
+    Error,
+    /// The story can be played, but probably not as intended.
+    Warning,
+}
+
+/// The kinds of problem the linter can find in a story.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// A story file couldn't be read or parsed.
+    ParseError,
+    /// An option or a check of visits points to a block or story file that doesn't exist.
+    MissingTarget,
+    /// A story file contains two blocks with the same name.
+    DuplicateBlock,
+    /// No option leads to a block.
+    UnreachableBlock,
+    /// A condition or counter update reads a flag that nothing sets.
+    UnsetFlag,
+    /// A condition or counter update reads a counter that nothing sets.
+    UnsetCounter,
+    /// A placeholder shows a variable that nothing sets.
+    UnknownVariable,
+    /// A line starts with something that looks like markup, but is read as plain text.
+    UnknownPrefix,
+}
+
+/// A single problem found in a story.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Problem {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// What sort of problem it is.
+    pub kind: ProblemKind,
+    /// The story file the problem was found in.
+    pub file: String,
+    /// The line the problem was found on, if it could be pinned down.
+    pub line: Option<usize>,
+    /// The block the problem was found in, if any.
+    pub block: Option<String>,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity: &str = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        write!(f, "{}: {}", severity, self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(block) = &self.block {
+            write!(f, " (in block `{}`)", block)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every problem found in a story, in the order they were found.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// The story files that were checked, in the order they were reached.
+    pub files: Vec<String>,
+    /// The problems found in those files.
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    /// Returns true if any problem found is an error, rather than just a warning.
+    ///
+    /// ```
+    /// # use intfic::lint::Report;
+    /// assert_eq!(Report::default().has_errors(), false);
+    /// ```
+    pub fn has_errors(&self) -> bool {
+        self.problems.iter().any(|problem| problem.severity == Severity::Error)
+    }
+
+    /// Returns the report as pretty-printed JSON, for other tools to read.
+    ///
+    /// ```
+    /// # use intfic::lint::Report;
+    /// assert_eq!(Report::default().to_json(), "{\n  \"files\": [],\n  \"problems\": []\n}");
+    /// ```
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Serialization failed")
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for problem in self.problems.iter() {
+            writeln!(f, "{}", problem)?;
+        }
+
+        let errors = self.problems.iter().filter(|problem| problem.severity == Severity::Error).count();
+        write!(
+            f,
+            "Checked {} story file(s): {} error(s), {} warning(s)",
+            self.files.len(),
+            errors,
+            self.problems.len() - errors,
+        )
+    }
+}
+
+// A story file that has been read and parsed.
+struct StoryFile {
+    lines: Vec<String>,
+    blocks: Vec<StoryBlock>,
+}
+
+/// Loads every story file reachable from the GameState's progress with the given Loader, and reports any problems found in them.
+///
+/// The flags and counters already in the GameState are treated as set, so conditions on them are not reported.
+/// If the progress doesn't name a block, the story starts at the first block of the file.
+///
+/// ```no_run
+/// # use intfic::game_state::GameState;
+/// # use intfic::lint::check;
+/// # use intfic::parse_file::Loader;
+/// let mut game: GameState = GameState::new("Test GameState");
+/// game.set_progress("example_1.txt", "start");
+///
+/// let report = check(&game, &Loader::new("resources"));
+/// println!("{}", report);
+/// assert_eq!(report.has_errors(), false);
+/// ```
+pub fn check(game: &GameState, loader: &Loader) -> Report {
+    let mut report: Report = Report::default();
+    let mut files: HashMap<String, StoryFile> = HashMap::new();
+    let mut failed: HashMap<String, ParseError> = HashMap::new();
+    let mut reached: HashSet<(String, String)> = HashSet::new();
+    let mut queue: VecDeque<(String, String)> = VecDeque::new();
+
+    let (start_file, start_block) = game.progress.clone();
+    if load(&start_file, loader, &mut files, &mut failed, &mut report) {
+        if let Some(block) = find_block(&files[&start_file].blocks, &start_block) {
+            queue.push_back((start_file.clone(), block));
+        } else {
+            report.problems.push(Problem {
+                severity: Severity::Error,
+                kind: ProblemKind::MissingTarget,
+                file: start_file.clone(),
+                line: None,
+                block: None,
+                message: format!("the story starts at block `{}`, which doesn't exist", start_block),
+            });
+        }
+    } else if is_missing(&start_file, &failed) {
+        report.problems.push(Problem {
+            severity: Severity::Error,
+            kind: ProblemKind::MissingTarget,
+            file: start_file.clone(),
+            line: None,
+            block: None,
+            message: String::from("the story starts in this file, which couldn't be read"),
+        });
+    }
+
+    while let Some((file, name)) = queue.pop_front() {
+        if !reached.insert((file.clone(), name.clone())) {
+            continue;
+        }
+
+        let results: Vec<String> = files[&file]
+            .blocks
+            .iter()
+            .find(|block| block.name == name)
+            .map(|block| block.choices().into_iter().flat_map(|choice| targets(&choice.result)).map(String::from).collect())
+            .unwrap_or_default();
+
+        for result in results {
+            let (target_file, target_block) = match split_target(&result) {
+                Some((target_file, target_block)) => (String::from(target_file), target_block),
+                None => (file.clone(), result.as_str()),
+            };
+
+            if load(&target_file, loader, &mut files, &mut failed, &mut report) {
+                if let Some(block) = find_block(&files[&target_file].blocks, target_block) {
+                    queue.push_back((target_file, block));
+                    continue;
+                }
+            } else if !is_missing(&target_file, &failed) {
+                continue;
+            }
+
+            report.problems.push(Problem {
+                severity: Severity::Error,
+                kind: ProblemKind::MissingTarget,
+                file: file.clone(),
+                line: option_line(&files[&file].lines, &name, &result),
+                block: Some(name.clone()),
+                message: format!("option leads to `{}`, which doesn't exist", result),
+            });
+        }
+    }
+
+    let mut set_flags: HashSet<String> = game.flags.keys().cloned().collect();
+    let mut set_counters: HashSet<String> = game.counters.keys().cloned().collect();
+    let mut set_strings: HashSet<String> = game.strings.keys().cloned().collect();
+    set_flags.extend(SYSTEM_FLAGS.iter().map(|flag| String::from(*flag)));
+    for story_file in files.values() {
+        for block in story_file.blocks.iter() {
+            for statement in block.statements() {
+                match statement {
+                    Statement::SetFlag(name, _) => set_flags.insert(name.clone()),
+                    Statement::Counter(name, _) => set_counters.insert(name.clone()),
+                    Statement::Prompt(prompt) => set_strings.insert(prompt.variable.clone()),
+                    _ => continue,
+                };
+            }
+        }
+    }
+
+    for filename in report.files.clone() {
+        let story_file: &StoryFile = &files[&filename];
+        check_blocks(&filename, story_file, &reached, &mut report);
+        check_lines(&filename, story_file, &set_flags, &set_counters, &set_strings, &mut report);
+    }
+
+    report
+}
+
+// Reads and parses a story file if we haven't already, returning true if it is available.
+//
+// Files that couldn't be read are left for the caller to report, while malformed files are reported here.
+fn load(
+    filename: &str,
+    loader: &Loader,
+    files: &mut HashMap<String, StoryFile>,
+    failed: &mut HashMap<String, ParseError>,
+    report: &mut Report,
+) -> bool {
+    if files.contains_key(filename) {
+        return true;
+    } else if failed.contains_key(filename) {
+        return false;
+    }
+
+    let parsed = loader.read(filename).and_then(|lines| {
+        let blocks: Vec<StoryBlock> = loader.parse(filename, &lines)?;
+        Ok(StoryFile { lines, blocks })
+    });
+
+    match parsed {
+        Ok(story_file) => {
+            files.insert(String::from(filename), story_file);
+            report.files.push(String::from(filename));
+            true
+        }
+        Err(e) => {
+            if let ParseError::Syntax { line, message, .. } = &e {
+                report.problems.push(Problem {
+                    severity: Severity::Error,
+                    kind: ProblemKind::ParseError,
+                    file: String::from(filename),
+                    line: Some(*line),
+                    block: None,
+                    message: message.clone(),
+                });
+            }
+
+            failed.insert(String::from(filename), e);
+            false
+        }
+    }
+}
+
+// Returns the name of the block with the given name, or of the first block if the name is empty.
+fn find_block(blocks: &[StoryBlock], name: &str) -> Option<String> {
+    blocks
+        .iter()
+        .find(|block| name.is_empty() || block.name == name)
+        .map(|block| block.name.clone())
+}
+
+// Returns true if the given story file couldn't be read at all.
+fn is_missing(filename: &str, failed: &HashMap<String, ParseError>) -> bool {
+    matches!(failed.get(filename), Some(ParseError::Io { .. }))
+}
+
+// Reports blocks with duplicate names, and blocks no option leads to.
+fn check_blocks(filename: &str, story_file: &StoryFile, reached: &HashSet<(String, String)>, report: &mut Report) {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let titles: Vec<usize> = (0..story_file.lines.len())
+        .filter(|num| story_file.lines[*num].starts_with(":- "))
+        .collect();
+
+    for (num, block) in story_file.blocks.iter().enumerate() {
+        let line: Option<usize> = titles.get(num).map(|title| title + 1);
+
+        if !seen.insert(&block.name) {
+            report.problems.push(Problem {
+                severity: Severity::Error,
+                kind: ProblemKind::DuplicateBlock,
+                file: String::from(filename),
+                line,
+                block: Some(block.name.clone()),
+                message: format!("another block in this file is already named `{}`", block.name),
+            });
+        } else if !reached.contains(&(String::from(filename), block.name.clone())) {
+            report.problems.push(Problem {
+                severity: Severity::Warning,
+                kind: ProblemKind::UnreachableBlock,
+                file: String::from(filename),
+                line,
+                block: Some(block.name.clone()),
+                message: String::from("no option leads to this block"),
+            });
+        }
+    }
+}
+
+// Reports conditions on flags or counters that are never set, flags and counters read by updates that are never set,
+// placeholders for variables that are never set, and lines that look like unknown markup.
+fn check_lines(
+    filename: &str,
+    story_file: &StoryFile,
+    set_flags: &HashSet<String>,
+    set_counters: &HashSet<String>,
+    set_strings: &HashSet<String>,
+    report: &mut Report,
+) {
+    let mut block: Option<String> = None;
+
+    for (num, line) in story_file.lines.iter().enumerate() {
+        if let Some(name) = line.strip_prefix(":- ") {
+            block = Some(String::from(name));
+            continue;
+        }
+
+        let mut problem = |kind: ProblemKind, message: String| {
+            report.problems.push(Problem {
+                severity: Severity::Warning,
+                kind,
+                file: String::from(filename),
+                line: Some(num + 1),
+                block: block.clone(),
+                message,
+            })
+        };
+
+        let keyword: &str = line.split(' ').next().unwrap_or_default();
+        let conditions: Vec<&str> = if SECTIONS.contains(&keyword) {
+            line[keyword.len()..].split(',').collect()
+        } else {
+            let shown: &str = match line.get(..2).unwrap_or_default() {
+                "*-" => line.split(" -> ").next().unwrap_or_default(),
+                "$-" => line.split(" => ").nth(1).unwrap_or_default(),
+                "?-" | "#-" | "~-" => line,
+                prefix if PREFIXES.contains(&prefix) => "",
+                _ => line,
+            };
+            for name in template::variables(shown).unwrap_or_default() {
+                if !set_flags.contains(name) && !set_counters.contains(name) && !set_strings.contains(name) {
+                    problem(ProblemKind::UnknownVariable, format!("`{{{}}}` shows a variable that is never set", name));
+                }
+            }
+
+            let conditionals: Vec<&str> = if let Some(choice) = line.strip_prefix("*- ") {
+                let choice_split: Vec<&str> = choice.split(" -> ").collect();
+                vec![ChoiceKind::split_marker(choice_split[0]).1, choice_split.get(2).copied().unwrap_or_default()]
+            } else if let Some(result) = line.strip_prefix("-> ") {
+                vec![result]
+            } else if line.starts_with("?-") || line.starts_with("#-") {
+                vec![line]
+            } else {
+                if let Some(prefix) = unknown_prefix(line) {
+                    problem(
+                        ProblemKind::UnknownPrefix,
+                        format!("`{}` isn't a known prefix, so this line will be shown as text", prefix),
+                    );
+                }
+                continue;
+            };
+
+            conditionals
+                .iter()
+                .flat_map(|conditional| conditional.split(" => "))
+                .filter_map(|segment| segment.strip_prefix("?- ").or_else(|| segment.strip_prefix("#- ")))
+                .collect()
+        };
+
+        // A switch compares numbers, so every name in its value and cases is a counter.
+        let numeric: bool = keyword == "switch-" || keyword == "case-";
+        for condition in conditions {
+            let expr: Expr = match Expr::parse(condition.trim()) {
+                Ok(expr) => expr,
+                Err(_) => continue,
+            };
+            let (flags, mut counters) = if numeric { (Vec::new(), expr.flags()) } else { (expr.flags(), Vec::new()) };
+            counters.extend(expr.counters());
+
+            for flag in flags.into_iter().filter(|flag| !set_flags.contains(*flag)) {
+                problem(ProblemKind::UnsetFlag, format!("flag `{}` is checked, but never set", flag));
+            }
+            for counter in counters.into_iter().filter(|counter| !set_counters.contains(*counter)) {
+                problem(ProblemKind::UnsetCounter, format!("counter `{}` is checked, but never set", counter));
+            }
+            for block in expr.blocks().into_iter().filter(|block| split_target(block).is_none()) {
+                if find_block(&story_file.blocks, block).is_none() {
+                    problem(ProblemKind::MissingTarget, format!("visits of block `{}` are checked, but it doesn't exist", block));
+                }
+            }
+        }
+    }
+
+    for block in story_file.blocks.iter() {
+        for statement in block.statements() {
+            let (name, update) = match statement {
+                Statement::Counter(name, update) => (name, update),
+                _ => continue,
+            };
+
+            for flag in update.flags().into_iter().filter(|flag| !set_flags.contains(*flag)) {
+                report.problems.push(Problem {
+                    severity: Severity::Warning,
+                    kind: ProblemKind::UnsetFlag,
+                    file: String::from(filename),
+                    line: update_line(&story_file.lines, &block.name, name, flag),
+                    block: Some(block.name.clone()),
+                    message: format!("flag `{}` is read, but never set", flag),
+                });
+            }
+            for counter in update.counters().into_iter().filter(|counter| !set_counters.contains(*counter)) {
+                report.problems.push(Problem {
+                    severity: Severity::Warning,
+                    kind: ProblemKind::UnsetCounter,
+                    file: String::from(filename),
+                    line: update_line(&story_file.lines, &block.name, name, counter),
+                    block: Some(block.name.clone()),
+                    message: format!("counter `{}` is read, but never set", counter),
+                });
+            }
+        }
+    }
+}
+
+// Finds the line of the option in the given block that may lead to the given target.
+fn option_line(lines: &[String], block: &str, result: &str) -> Option<usize> {
+    let title: usize = lines.iter().position(|line| line.strip_prefix(":- ") == Some(block))?;
+
+    lines[title + 1..]
+        .iter()
+        .take_while(|line| !line.starts_with(":- "))
+        .position(|line| {
+            let written: &str = match line.strip_prefix("*- ") {
+                Some(choice) => choice.split(" -> ").nth(2).unwrap_or_default(),
+                None => line.strip_prefix("-> ").unwrap_or_default(),
+            };
+            targets(written).contains(&result)
+        })
+        .map(|num| title + num + 2)
+}
+
+// Finds the line in the given block that updates the given counter with a value reading the other given counter,
+// on its own or in the effects of an option.
+fn update_line(lines: &[String], block: &str, counter: &str, read: &str) -> Option<usize> {
+    let title: usize = lines.iter().position(|line| line.strip_prefix(":- ") == Some(block))?;
+
+    lines[title + 1..]
+        .iter()
+        .take_while(|line| !line.starts_with(":- "))
+        .position(|line| {
+            let effects: Vec<&str> = match line.strip_prefix("*- ") {
+                Some(choice) => choice.split(" -> ").nth(3).unwrap_or_default().split(';').map(str::trim).collect(),
+                None => vec![line.as_str()],
+            };
+            effects.iter().filter_map(|effect| effect.strip_prefix("+- ")).any(|update| {
+                let mut words = update.split(|c: char| !c.is_alphanumeric() && c != '_').filter(|word| !word.is_empty());
+                words.next() == Some(counter) && words.any(|word| word == read)
+            })
+        })
+        .map(|num| title + num + 2)
+}
+
+// Returns the start of a line if it looks like markup, but isn't anything the parser or text reader understands.
+fn unknown_prefix(line: &str) -> Option<&str> {
+    let is_marker = |c: char| "=+*?#:-~$%&!<>|@^".contains(c);
+    let mut chars = line.chars();
+
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(a), Some(b), _) if is_marker(a) && is_marker(b) && !PREFIXES.contains(&&line[..2]) => Some(&line[..2]),
+        (Some('-'), Some(c), Some(' ')) if c.is_ascii_lowercase() && !COLORS.contains(&&line[..3]) => Some(&line[..3]),
+        _ => None,
+    }
+}
@@ -1,13 +1,88 @@
+use std::env;
+use std::process;
+
+use intfic::engine::Engine;
+use intfic::frontend::Terminal;
 use intfic::game_state::GameState;
+use intfic::lint::check;
+use intfic::manifest::Manifest;
+use intfic::parse_file::Loader;
+
+// Flags that are followed by a value.
+const VALUED_FLAGS: [&str; 2] = ["--root", "--save-dir"];
 
 fn main() {
-    // Create an empty gamestate with the title "Interactive Fiction Title"
-    let mut game = GameState::new("Interactive Fiction Title");
+    // "intfic [story dir]" plays the story in the given directory, "resources" by default.
+    // "--root <dir>" may be used instead, and also sets the directory for "check".
+    // "--save-dir <dir>" keeps saves in the given directory instead of the local data directory.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let loader: Loader = match (args.first().map(String::as_str), positional(&args).next()) {
+        (Some("check"), _) | (_, None) => flag_value(&args, "--root").map_or_else(Loader::default, Loader::new),
+        (_, Some(dir)) => Loader::new(dir),
+    };
+
+    // "intfic check [story file or file.txt#block] [block] [--json]" reports problems in the story instead of playing it.
+    if args.first().map(String::as_str) == Some("check") {
+        process::exit(check_story(&args[1..], &loader));
+    }
+
+    // Create a gamestate from the story's "story.ron" manifest, which says where the story begins, then start the game in the terminal.
+    let mut engine = match Engine::from_manifest(loader) {
+        Ok(engine) => engine,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(dir) = flag_value(&args, "--save-dir") {
+        engine.game.set_save_dir(dir);
+    }
+    engine.run(&mut Terminal);
+
+    // Print out the GameState when the game is over, whether the story ended or the player quit.
+    engine.game.print_debug();
+}
 
-    // Set the game progress to indicate the story file and block we want to start with, then starting the game.
-    game.set_progress("example_1.txt", "start");
-    game.start();
+// Checks every story file reachable from the given start, printing a report and returning the exit code.
+fn check_story(args: &[String], loader: &Loader) -> i32 {
+    let json: bool = args.iter().any(|arg| arg == "--json");
+    let mut start = positional(args);
+
+    // Start from the manifest's starting point unless told otherwise, and hold story files to its declared variables.
+    let mut loader = loader.clone();
+    let mut game = match Manifest::load(&loader) {
+        Ok(manifest) => {
+            loader.variables = manifest.variables.clone();
+            GameState::from_manifest(&manifest)
+        }
+        Err(_) => GameState::default(),
+    };
+    if let Some(story) = start.next() {
+        game.set_progress_ref(story);
+    }
+    if let Some(block) = start.next() {
+        game.progress.1 = String::from(block);
+    }
+
+    let report = check(&game, &loader);
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        println!("{}", report);
+    }
+
+    if report.has_errors() { 1 } else { 0 }
+}
+
+// Returns the value following the given flag, if it was passed.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1)).map(String::as_str)
+}
 
-    // Print out the GameState when the game is over. This may not run if the player exits early!
-    game.print_debug();
+// Returns the arguments that aren't flags or the values of flags.
+fn positional(args: &[String]) -> impl Iterator<Item = &str> {
+    args.iter()
+        .enumerate()
+        .filter(move |(i, arg)| !arg.starts_with("--") && (*i == 0 || !VALUED_FLAGS.contains(&args[i - 1].as_str())))
+        .map(|(_, arg)| arg.as_str())
 }
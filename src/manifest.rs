@@ -0,0 +1,172 @@
+use ron::de::from_str;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::parse_file::{Loader, ParseError};
+
+/// Describes a story as a whole: what it's called, who wrote it, where it starts, and which story files it's made of.
+///
+/// A manifest lives alongside the story files as "story.ron", so a story directory can be played without writing any Rust.
+/// Only `title` and `start` are required, everything else defaults to empty.
+///
+/// "story.ron":
+/// ```ron
+/// (
+///     title: "Interactive Fiction Title",
+///     author: "tjhaskel",
+///     version: "1.0.0",
+///     start: ("example_1.txt", "start"),
+///     flags: {
+///         "computer_access": false,
+///     },
+///     counters: {
+///         "score": 0,
+///     },
+///     variables: {
+///         "health": Counter(default: 10, min: 0, max: 10),
+///         "has_key": Flag(default: false),
+///         "player_name": Text(default: "Sam"),
+///     },
+///     files: ["example_1.txt", "example_2.txt"],
+/// )
+/// ```
+///
+/// Once a story declares any variables, every flag, counter, or string its story files change must be declared,
+/// so a misspelled name is a ParseError when the story is loaded instead of a silent bug.
+#[derive(Debug, Default, Clone, Deserialize, PartialEq, Serialize)]
+pub struct Manifest {
+    /// The name of the story, also used as the name of the GameState and its save file.
+    pub title: String,
+
+    /// Who wrote the story.
+    #[serde(default)]
+    pub author: String,
+
+    /// The version of the story, so saves and bug reports can tell releases apart.
+    #[serde(default)]
+    pub version: String,
+
+    /// The story file and block the story begins with. An empty block name starts at the first block of the file.
+    pub start: (String, String),
+
+    /// Flags set before the story begins.
+    #[serde(default)]
+    pub flags: HashMap<String, bool>,
+
+    /// Counters set before the story begins.
+    #[serde(default)]
+    pub counters: HashMap<String, i32>,
+
+    /// Strings set before the story begins.
+    #[serde(default)]
+    pub strings: HashMap<String, String>,
+
+    /// Flags, counters, and strings the story uses, with their starting values and the limits counters are kept within.
+    #[serde(default)]
+    pub variables: HashMap<String, Variable>,
+
+    /// Every story file that makes up the story, including ones only reached by loading a save.
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+/// The declaration of a flag, counter, or string in a story's manifest.
+#[derive(Debug, Clone, Deserialize, PartialEq, Serialize)]
+pub enum Variable {
+    /// A flag, which starts false unless given a default.
+    Flag {
+        /// The value the flag starts with.
+        #[serde(default)]
+        default: bool,
+    },
+    /// A counter, which starts at 0 unless given a default, and is kept between its min and max if given.
+    Counter {
+        /// The value the counter starts with.
+        #[serde(default)]
+        default: i32,
+        /// The lowest value the counter may take.
+        #[serde(default = "i32_min")]
+        min: i32,
+        /// The highest value the counter may take.
+        #[serde(default = "i32_max")]
+        max: i32,
+    },
+    /// A string, which starts empty unless given a default.
+    Text {
+        /// The value the string starts with.
+        #[serde(default)]
+        default: String,
+    },
+}
+
+impl Variable {
+    /// Returns the kind of variable this declares, as it is described in error messages.
+    ///
+    /// ```
+    /// # use intfic::manifest::Variable;
+    /// assert_eq!(Variable::Flag { default: false }.kind(), "flag");
+    /// assert_eq!(Variable::Text { default: String::new() }.kind(), "string");
+    /// ```
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Variable::Flag { .. } => "flag",
+            Variable::Counter { .. } => "counter",
+            Variable::Text { .. } => "string",
+        }
+    }
+}
+
+// The lowest value a counter may take when its declaration doesn't say.
+fn i32_min() -> i32 {
+    i32::MIN
+}
+
+// The highest value a counter may take when its declaration doesn't say.
+fn i32_max() -> i32 {
+    i32::MAX
+}
+
+impl Manifest {
+    /// The name of the manifest file within a story directory.
+    pub const FILENAME: &'static str = "story.ron";
+
+    /// Reads and parses the manifest of the story the given Loader finds files in.
+    ///
+    /// ```no_run
+    /// # use intfic::manifest::Manifest;
+    /// # use intfic::parse_file::Loader;
+    /// let manifest: Manifest = Manifest::load(&Loader::new("resources")).unwrap();
+    ///
+    /// assert_eq!(manifest.start, (String::from("example_1.txt"), String::from("start")));
+    /// ```
+    pub fn load(loader: &Loader) -> Result<Manifest, ParseError> {
+        Manifest::parse(&loader.read(Manifest::FILENAME)?.join("\n"))
+    }
+
+    /// Parses a manifest from a string of RON, returning a ParseError pointing at the problem if it's malformed.
+    ///
+    /// ```
+    /// # use intfic::manifest::Manifest;
+    /// # use intfic::parse_file::ParseError;
+    /// let manifest: Manifest = Manifest::parse("(title: \"Test Story\", start: (\"start.txt\", \"\"))").unwrap();
+    /// assert_eq!(manifest.title, String::from("Test Story"));
+    /// assert!(manifest.files.is_empty());
+    ///
+    /// match Manifest::parse("(title: \"Test Story\")") {
+    ///     Err(ParseError::Syntax { line, message, .. }) => assert_eq!((line, message.as_str()), (1, "missing field `start`")),
+    ///     _ => panic!("expected a syntax error"),
+    /// }
+    /// ```
+    pub fn parse(contents: &str) -> Result<Manifest, ParseError> {
+        from_str(contents).map_err(|e| {
+            let line: usize = e.position.line.max(1);
+            ParseError::Syntax {
+                filename: String::from(Manifest::FILENAME),
+                line,
+                column: e.position.col.max(1),
+                text: String::from(contents.lines().nth(line - 1).unwrap_or_default()),
+                message: e.code.to_string(),
+            }
+        })
+    }
+}
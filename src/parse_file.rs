@@ -33,6 +33,8 @@
 //! 
 //! ## TEXT & EFFECTS
 //! The middle section of a Story Block contains the text the player will see, and any effects that will be applied to the GameState.
+//! Lines are read top to bottom, and each effect is applied as it's reached, so conditions see every effect written above them.
+//! In the example below, the `#- score >= 50` line is checked before **shame** goes up, and repeating `+- shame + 1` would add 2 in total.
 //! <pre>
 //! He abruptly yanks the power cord out of the computer and power strip, it shuts off with a sharp buzz.
 //! ?- saved_work => Thank god you had just saved, you can't imagine having lost all that work. => You can't believe what just happened. Why didn't you save? So much work just gone.
@@ -54,20 +56,83 @@
 //! * `"-y "`: Prints the line in <span style="color:yellow; text-shadow: 1px 0.5px #555">yellow</span>.
 //! * `"=- "`: Sets the given flag to the given value in our GameState
 //!   > **Example:** `"=- computer_access = false"` sets **computer_access** to **false**.
-//! * `"+- "`: Adds the given value to the given counter in our GameState
-//!   > **Example:** `"+- shame + 1"` adds **1** to whatever value **shame** has, or sets it to **1** if it is not set.
-//! * `"?- "`: Prints a "then" or optional "else" line based on the given flag's value in our GameState.
+//! * `"+- "`: Adds to, subtracts from, multiplies, or sets the given counter in our GameState
+//!   > **Example:** `"+- shame + 1"` adds **1** to whatever value **shame** has, or sets it to **1** if it is not set.\
+//!   > **Example:** `"+- health - damage * 2"` subtracts twice the value of **damage** from **health**.\
+//!   > **Example:** `"+- gold = stash"` sets **gold** to the value of **stash**.\
+//!   > &nbsp;&nbsp; The value may be a whole number, another counter, or any calculation a condition could use.\
+//!   > &nbsp;&nbsp; Counters declared with a min and max in the story's [manifest](../manifest/struct.Manifest.html) are kept between them.
+//! * `"$- "`: Asks the player to type an answer in their own words, and stores it as a string in our GameState.
+//!   > **Example:** `"$- player_name => What's your name? => nonempty, max 20"`\
+//!   > &nbsp;&nbsp; After the block's text, the player is asked `"What's your name?"`, and their answer is stored as **player_name**.\
+//!   > &nbsp;&nbsp; The rules after the question are optional: `nonempty` requires an answer, and `max 20` limits it to 20 characters.\
+//!   > &nbsp;&nbsp; The player is asked again until their answer follows the rules, then the block's lines carry on.\
+//!   > &nbsp;&nbsp; So the lines below a question may show or check the answer.
+//! * `"~- "`: Prints a different line each time the block is read, from a sequence of lines separated by `" => "`.
+//!   > **Example:** `"~- stopping => The bell tolls once. => The bell tolls again. => -y The bell won't stop tolling."`\
+//!   > &nbsp;&nbsp; A `stopping` sequence prints each line in turn, then keeps printing the last one.\
+//!   > &nbsp;&nbsp; A `cycle` sequence starts over from the first line after the last,\
+//!   > &nbsp;&nbsp; &nbsp;&nbsp; a `shuffle` sequence prints every line once in a random order before shuffling them again,\
+//!   > &nbsp;&nbsp; &nbsp;&nbsp; and a `once` sequence prints each line in turn, then nothing at all.\
+//!   > &nbsp;&nbsp; Lines of a sequence may use colors and placeholders, but not conditions.\
+//!   > &nbsp;&nbsp; Where each sequence is up to is saved along with the rest of our GameState.
+//! * `"?- "`: Prints a "then" or optional "else" line based on the given condition's value in our GameState.
 //!   > **Example:** `"?- saved_work => saved_work then line => saved_work else line"`\
 //!   > &nbsp;&nbsp; This will print `"saved_work then line"` if **saved_work** is **true**,\
 //!   > &nbsp;&nbsp; &nbsp;&nbsp; and will print `"saved_work else line"` otherwise.\
 //!   > &nbsp;&nbsp; The "else" line is optional, if you would rather no line be read should the condition fail.\
 //!   > &nbsp;&nbsp; Note that conditional lines are parsed *recursively*, so you may use colors or nested conditionals in them.
-//! * `"#- "`: Prints a "then" or optional "else" line based on the given predicate's value in our GameState's counter environment.
+//! * `"#- "`: The same as `"?- "`, traditionally used for conditions on counters.
 //!   > **Example:** `"#- score >= 50 => score check then line => score check else line"`\
 //!   > &nbsp;&nbsp; This will print `"score check then line"` if **score >= 50** is **true**,\
-//!   > &nbsp;&nbsp; &nbsp;&nbsp; and will print `"score check else line"` otherwise.\
-//!   > &nbsp;&nbsp; The "else" line is optional, if you would rather no line be read should the condition fail.\
-//!   > &nbsp;&nbsp; Note that conditional lines are parsed *recursively*, so you may use colors or nested conditionals in them.
+//!   > &nbsp;&nbsp; &nbsp;&nbsp; and will print `"score check else line"` otherwise.
+//! 
+//! Conditions may combine flags, counters, and whole numbers with `and`, `or`, `not`, comparisons, arithmetic, and parentheses.
+//! A name used as a number is a counter, and a name used as a truth value is a flag.
+//! The number of times a block has been read is counted automatically, and checked with `visits(block)`, `visited(block)`, and `first_visit`.
+//! See [Expr](../expr/enum.Expr.html) for the full syntax.
+//!   > **Example:** `"?- has_key and not door_open => You unlock the door."`\
+//!   > **Example:** `"#- strength > enemy_strength * 2 => -r You overpower them easily."`\
+//!   > **Example:** `"?- visited(cellar) and not first_visit => The cellar door still hangs open."`
+//! 
+//! Text, conditional lines, options, and questions may show the current value of a string, counter, or flag with a placeholder.
+//! See [interpolate](../template/fn.interpolate.html) for the full syntax.
+//!   > **Example:** `"Welcome back, {player_name}. You have {gold} {gold|coin|coins}."`\
+//!   > &nbsp;&nbsp; `{gold|coin|coins}` shows `"coin"` if **gold** is **1**, and `"coins"` otherwise.\
+//!   > &nbsp;&nbsp; `{score:5}` pads **score** to 5 characters, and `{{` or `}}` show a brace.\
+//!   > &nbsp;&nbsp; A placeholder for a variable that hasn't been set shows a red error in place of the line.
+//! 
+//! ## SECTIONS
+//! A condition may guard a whole section of a block, rather than a single line.
+//! Sections may hold text, effects, questions, options, and other sections.
+//! <pre>
+//! if- has_key and not door_open
+//! You turn the key, and the lock gives way with a click.
+//! =- door_open = true
+//! *- Step inside -> inside, enter -> hallway
+//! elif- strength > 10
+//! -r You throw your shoulder against the door until it splinters.
+//! =- door_open = true
+//! else-
+//! The door won't budge.
+//! endif-
+//! </pre>
+//! Only the lines of the first `"if- "` or `"elif- "` whose condition passes are read, or those after `"else-"` if none do.
+//! `"elif- "` and `"else-"` are optional, and every `"if- "` must be closed with `"endif-"`.
+//! 
+//! A counter may be matched against a list of values with a switch:
+//! <pre>
+//! switch- gold
+//! case- 0
+//! Your purse is empty.
+//! case- 1, 2, 3
+//! A few coins jingle in your purse.
+//! default-
+//! Your purse is heavy with {gold} coins.
+//! endswitch-
+//! </pre>
+//! The lines of the first `"case- "` with a value equal to the switched value are read, or those after `"default-"` if none are.
+//! Values may be any calculation a condition could use, and every `"switch- "` must be closed with `"endswitch-"`.
 //! 
 //! ## QUESTION & OPTIONS
 //! The final section of a StoryBlock is the question and options presented.
@@ -97,6 +162,10 @@
 //!   > &nbsp;&nbsp; If a match is found, then the Story File `"wander_neighborhood.txt"` will be loaded\
 //!   > &nbsp;&nbsp; &nbsp;&nbsp; and the story will pick up at the first block of that file.
 //! 
+//!   > **Example:** `"*- Sneak out the back. -> sneak -> wander_neighborhood.txt#alley"`\
+//!   > &nbsp;&nbsp; A Story File may be followed by `#` and the name of a block in it, to pick up the story at that block instead.\
+//!   > &nbsp;&nbsp; This works for `"-> "` lines too, such as `"-> wander_neighborhood.txt#alley"`.
+//! 
 //!   > **Example:** `"*- Go to bed. ->  -> sleep"`\
 //!   > &nbsp;&nbsp; This option will be presented as `"#) Go to bed."` to the player,\
 //!   > &nbsp;&nbsp; &nbsp;&nbsp; Where "#" is the number it is presented as, either **2** or **3** in the above example.\
@@ -107,6 +176,22 @@
 //!   > &nbsp;&nbsp; &nbsp;&nbsp; You may do so as long as you keep two spaces between the "->" delimiters.\
 //!   > &nbsp;&nbsp; If a match is found, then the Story Block `"sleep"` will be played.
 //! 
+//! Options may also set flags and update counters when they're chosen, with a fourth part holding effects separated by `";"`:
+//! <pre>
+//! *- Text the player will see -> strings to match input -> block or file to read if matched -> effects
+//! </pre>
+//!   > **Example:** `"*- Buy the sword. -> buy, sword -> shop -> +- gold - 10; =- has_sword = true"`\
+//!   > &nbsp;&nbsp; If this option is chosen, **gold** goes down by **10** and **has_sword** is set to **true**,\
+//!   > &nbsp;&nbsp; &nbsp;&nbsp; before the Story Block `"shop"` is read, so its lines already see the sword.\
+//!   > &nbsp;&nbsp; Effects are written just like the `"=- "` and `"+- "` lines of a block's text.
+//! 
+//! The result of an option, or of a `"-> "` line, may pick between two targets with a condition, checked once any effects are applied:
+//!   > **Example:** `"-> ?- has_key => vault => locked_door"`\
+//!   > &nbsp;&nbsp; This reads the Story Block `"vault"` if **has_key** is **true**, and `"locked_door"` otherwise.\
+//!   > &nbsp;&nbsp; Both targets are required, and either may lead to another Story File like `"chapter2.txt#vault"`.
+//! 
+//! Options may be written inside [sections](#sections), to offer them only if their branch is read.
+//! 
 //! The folowing character combinations, when used at the start of an option, have special effects:
 //! * `"?- "`: Presents the option only if the given flag's value is true in our GameState.
 //!   > **Example:** `"*- ?- have_time_machine => Time Travel -> go back -> time_fix"`\
@@ -116,118 +201,870 @@
 //!   > **Example:** `"*- #- strength >= 25 => Punch your dad. -> fight -> fight_dad.txt"`\
 //!   > &nbsp;&nbsp; This option will only be available to choose from if **strength >= 25** is **true**.\
 //!   > &nbsp;&nbsp; Note that their is no "else" option available to show.
+//! 
+//! The folowing markers, when used at the start of an option before any condition, change when it's offered:
+//! * `"[once] "`: Stops offering the option once the player has chosen it.
+//!   > **Example:** `"*- [once] Ask about the key. -> key -> ask_key"`\
+//!   > &nbsp;&nbsp; After asking about the key, the player won't be offered the question again when they come back to this block.\
+//!   > &nbsp;&nbsp; Which options have been chosen is saved along with the rest of our GameState.
+//! * `"[sticky] "`: Offers the option every time its block is read, the same as an option without a marker.
+//! * `"[fallback] "`: Offers the option only if no other option of its block is available.
+//!   > **Example:** `"*- [fallback] Say goodbye. -> bye -> farewell"`\
+//!   > &nbsp;&nbsp; Once every `[once]` question of the block has been asked, this is the only option left,\
+//!   > &nbsp;&nbsp; &nbsp;&nbsp; so the story carries on to **farewell** by itself.
+//! 
+//! ## ERRORS
+//! If a line doesn't follow this specification, loading the story file fails with a [ParseError](enum.ParseError.html)
+//! pointing at the file, line, and column of the problem:
+//! <pre>
+//! example_2.txt:20:22: expected `true` or `false`, found `flase`
+//!     =- computer_access = flase
+//!                          ^
+//! </pre>
 
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
-use std::path::Path;
-use text_io::read;
+use std::path::PathBuf;
 
+use crate::expr::{Expr, Op};
 use crate::game_state::GameState;
-use crate::story_block::{Choice, StoryBlock};
+use crate::manifest::{Manifest, Variable};
+use crate::story_block::{Choice, ChoiceKind, Conditional, Prompt, Section, Sequence, SequenceKind, Statement, StoryBlock, Update};
+use crate::template;
+use crate::util::stable_hash;
+
+/// Something that went wrong while reading or parsing a story file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The story file couldn't be opened or read.
+    Io {
+        /// The name of the story file.
+        filename: String,
+        /// A description of what went wrong.
+        message: String,
+    },
+    /// A line of the story file doesn't follow the Story File Markup Specification.
+    Syntax {
+        /// The name of the story file.
+        filename: String,
+        /// The line the problem was found on, starting at 1.
+        line: usize,
+        /// The character within the line the problem was found at, starting at 1.
+        column: usize,
+        /// The full text of the offending line.
+        text: String,
+        /// A description of what went wrong.
+        message: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io { filename, message } => write!(f, "Couldn't read {}: {}", filename, message),
+            ParseError::Syntax { filename, line, column, text, message } => write!(
+                f,
+                "{}:{}:{}: {}\n    {}\n    {}^",
+                filename, line, column, message, text, " ".repeat(column.saturating_sub(1)),
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// The byte offset and message of a problem found while parsing a single line.
+type LineResult = Result<(), (usize, String)>;
+
+// The words that open, continue, and close conditional sections.
+const SECTION_KEYWORDS: &[&str] = &["if-", "elif-", "else-", "endif-", "switch-", "case-", "default-", "endswitch-"];
+
+// A conditional section that has been opened but not closed yet, and the statements parsed into it so far.
+struct OpenSection {
+    // The line the section was opened on, and the keyword it was opened with, for describing unbalanced sections.
+    line: usize,
+    keyword: &'static str,
+    // The value each case of a switch is compared to.
+    subject: Option<Expr>,
+    section: Section,
+    // True once `else-` or `default-` has been reached.
+    otherwise: bool,
+}
+
+impl OpenSection {
+    // Returns the keyword that closes this section.
+    fn closer(&self) -> &'static str {
+        if self.keyword == "if-" {
+            "endif-"
+        } else {
+            "endswitch-"
+        }
+    }
+}
+
+/// Where story files are found when an option or the GameState's progress refers to them by name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// Story files are read from this directory, relative to the working directory unless absolute.
+    Directory(PathBuf),
+    /// Story files are looked up by name in this map of file contents, without touching the filesystem.
+    Files(HashMap<String, String>),
+}
+
+/// Finds and parses story files by name from a configurable Source.
+/// 
+/// The default Loader reads story files from the "resources" directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loader {
+    /// Where story files are found.
+    pub source: Source,
+    /// The flags, counters, and strings story files may change. If empty, story files may change any of them.
+    pub variables: HashMap<String, Variable>,
+}
+
+impl Default for Loader {
+    fn default() -> Loader {
+        Loader::new("resources")
+    }
+}
+
+impl Loader {
+    /// Creates a Loader that reads story files from the given directory.
+    /// 
+    /// ```
+    /// # use std::path::PathBuf;
+    /// # use intfic::parse_file::{Loader, Source};
+    /// let loader: Loader = Loader::new("/usr/share/my_game/story");
+    /// 
+    /// assert_eq!(loader.source, Source::Directory(PathBuf::from("/usr/share/my_game/story")));
+    /// ```
+    pub fn new<P: Into<PathBuf>>(root: P) -> Loader {
+        Loader {
+            source: Source::Directory(root.into()),
+            variables: HashMap::new(),
+        }
+    }
+
+    /// Creates a Loader that looks up story files by name in the given pairs of filenames and contents.
+    /// 
+    /// ```
+    /// # use intfic::parse_file::Loader;
+    /// let loader: Loader = Loader::from_files(vec![
+    ///     ("start.txt", ":- start\nHello!\n-> next.txt"),
+    ///     ("next.txt", ":- next\nGoodbye!"),
+    /// ]);
+    /// 
+    /// assert_eq!(loader.load("next.txt").unwrap()[0].name, String::from("next"));
+    /// ```
+    pub fn from_files<I, K, V>(files: I) -> Loader
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Loader {
+            source: Source::Files(files.into_iter().map(|(k, v)| (k.into(), v.into())).collect()),
+            variables: HashMap::new(),
+        }
+    }
+
+    /// Reads the lines of a story file without parsing them, returning a ParseError if the file couldn't be found or read.
+    /// 
+    /// ```
+    /// # use intfic::parse_file::Loader;
+    /// let loader: Loader = Loader::from_files(vec![("start.txt", ":- start\nHello!")]);
+    /// 
+    /// assert_eq!(loader.read("start.txt").unwrap(), vec![String::from(":- start"), String::from("Hello!")]);
+    /// assert!(loader.read("missing.txt").is_err());
+    /// ```
+    pub fn read(&self, filename: &str) -> Result<Vec<String>, ParseError> {
+        let io_error = |message: String| ParseError::Io {
+            filename: String::from(filename),
+            message,
+        };
+
+        match &self.source {
+            Source::Directory(root) => File::open(root.join(filename))
+                .and_then(|file| io::BufReader::new(file).lines().collect())
+                .map_err(|e| io_error(e.to_string())),
+            Source::Files(files) => match files.get(filename) {
+                Some(contents) => Ok(contents.lines().map(String::from).collect()),
+                None => Err(io_error(String::from("No such story file"))),
+            },
+        }
+    }
 
-/// Takes the name of a story file and parses it, returning Some(Vec\<StoryBlock>) if successful
+    /// Returns a fingerprint of the given story files' names and contents, which changes whenever any of them is edited.
+    /// 
+    /// The order the files are given in doesn't matter, and files that can't be read count as empty.
+    /// Saves store the fingerprint of the story they were made with, so the Engine can tell when the story has changed since.
+    /// 
+    /// ```
+    /// # use intfic::parse_file::Loader;
+    /// let loader: Loader = Loader::from_files(vec![("start.txt", ":- start\nHello!"), ("next.txt", ":- next\nGoodbye!")]);
+    /// let edited: Loader = Loader::from_files(vec![("start.txt", ":- start\nHello there!"), ("next.txt", ":- next\nGoodbye!")]);
+    /// 
+    /// assert_eq!(loader.fingerprint(&["start.txt", "next.txt"]), loader.fingerprint(&["next.txt", "start.txt"]));
+    /// assert_ne!(loader.fingerprint(&["start.txt", "next.txt"]), edited.fingerprint(&["start.txt", "next.txt"]));
+    /// ```
+    pub fn fingerprint<S: AsRef<str>>(&self, filenames: &[S]) -> u64 {
+        let mut filenames: Vec<&str> = filenames.iter().map(AsRef::as_ref).collect();
+        filenames.sort_unstable();
+        filenames.dedup();
+
+        let mut bytes: Vec<u8> = Vec::new();
+        for filename in filenames {
+            let lines: Vec<String> = self.read(filename).unwrap_or_default();
+            bytes.extend(filename.bytes().chain(lines.iter().flat_map(|line| line.bytes().chain(Some(b'\n')))).chain(Some(0)));
+        }
+        stable_hash(bytes)
+    }
+
+    /// Finds the story file with the given name and parses it, returning a ParseError describing the problem if unsuccessful.
+    /// 
+    /// ```no_run
+    /// # use intfic::parse_file::Loader;
+    /// let loader: Loader = Loader::new("resources");
+    /// 
+    /// match loader.load("example_1.txt") {
+    ///     Ok(loaded_blocks) => assert_eq!(loaded_blocks[0].name, String::from("start")),
+    ///     Err(e) => println!("{}", e),
+    /// }
+    /// ```
+    pub fn load(&self, filename: &str) -> Result<Vec<StoryBlock>, ParseError> {
+        self.parse(filename, &self.read(filename)?)
+    }
+
+    /// Parses the lines of a story file, returning a ParseError if it doesn't follow the specification
+    /// or changes a flag or counter this Loader's variables don't declare, including in the effects of options,
+    /// or reads an undeclared flag or counter in the value of a counter update.
+    /// 
+    /// ```
+    /// # use intfic::manifest::Variable;
+    /// # use intfic::parse_file::Loader;
+    /// let mut loader: Loader = Loader::from_files(Vec::<(String, String)>::new());
+    /// let lines: Vec<String> = vec![String::from(":- start"), String::from("+- helth - 1")];
+    /// let option: Vec<String> = vec![String::from(":- start"), String::from("*- Rest -> rest -> start -> +- helth + 1")];
+    /// let read: Vec<String> = vec![String::from(":- start"), String::from("+- health = health + bonus")];
+    /// assert!(loader.parse("start.txt", &lines).is_ok());
+    /// 
+    /// loader.variables.insert(String::from("health"), Variable::Counter { default: 10, min: 0, max: 10 });
+    /// assert!(loader.parse("start.txt", &lines).is_err());
+    /// assert!(loader.parse("start.txt", &option).is_err());
+    /// assert!(loader.parse("start.txt", &read).is_err());
+    /// ```
+    pub fn parse(&self, filename: &str, lines: &[String]) -> Result<Vec<StoryBlock>, ParseError> {
+        let blocks: Vec<StoryBlock> = parse_lines(filename, lines)?;
+
+        if self.variables.is_empty() {
+            return Ok(blocks);
+        }
+
+        for block in blocks.iter() {
+            for statement in block.statements() {
+                let (name, kind, prefix, reads): (&str, &str, &str, Vec<(&str, &str)>) = match statement {
+                    Statement::SetFlag(name, _) => (name, "flag", "=-", Vec::new()),
+                    Statement::Counter(name, update) => {
+                        let flags = update.flags().into_iter().map(|flag| (flag, "flag"));
+                        (name, "counter", "+-", flags.chain(update.counters().into_iter().map(|counter| (counter, "counter"))).collect())
+                    }
+                    Statement::Prompt(prompt) => (&prompt.variable, "string", "$-", Vec::new()),
+                    _ => continue,
+                };
+
+                let problem: Option<(&str, String)> = undeclared(&self.variables, name, kind, "changed")
+                    .map(|message| (name, message))
+                    .or_else(|| {
+                        reads
+                            .into_iter()
+                            .find_map(|(read, kind)| undeclared(&self.variables, read, kind, "read").map(|message| (read, message)))
+                    });
+                let (found, message) = match problem {
+                    Some(problem) => problem,
+                    None => continue,
+                };
+
+                let (num, mut at) = variable_line(lines, &block.name, prefix, name);
+                let text: String = lines.get(num).cloned().unwrap_or_default();
+                if found != name {
+                    at = word(&text, at + name.len(), found).unwrap_or(at);
+                }
+                return Err(ParseError::Syntax {
+                    filename: String::from(filename),
+                    line: num + 1,
+                    column: text.get(..at).unwrap_or(&text).chars().count() + 1,
+                    text,
+                    message,
+                });
+            }
+        }
+
+        Ok(blocks)
+    }
+}
+
+/// Takes the name of a story file in /resources and parses it, returning a ParseError describing the problem if unsuccessful.
+/// 
+/// Also updates the GameState progress with the name of the file.
+/// To load story files from somewhere else, use a [Loader](struct.Loader.html).
 /// 
 /// ```no_run
 /// # use intfic::game_state::GameState;
 /// # use intfic::parse_file::load_file;
-/// # use intfic::story_block::start_blocks;
 /// let mut game: GameState = GameState::new("Test GameState");
 /// 
-/// if let Some(loaded_blocks) = load_file("example_1.txt", &mut game) {
-///     start_blocks(&loaded_blocks, &mut game);
+/// match load_file("example_1.txt", &mut game) {
+///     Ok(loaded_blocks) => assert_eq!(loaded_blocks[0].name, String::from("start")),
+///     Err(e) => println!("{}", e),
 /// }
 /// ```
-pub fn load_file(filename: &str, game: &mut GameState) -> Option<Vec<StoryBlock>> {
-    if let Ok(lines) = get_file(filename) {
-        game.progress.0 = String::from(filename);
+pub fn load_file(filename: &str, game: &mut GameState) -> Result<Vec<StoryBlock>, ParseError> {
+    let blocks: Vec<StoryBlock> = Loader::default().load(filename)?;
+    game.progress.0 = String::from(filename);
+    Ok(blocks)
+}
+
+/// Parses the contents of a story file held in a string.
+/// 
+/// The filename is only used to describe where any problem was found.
+/// 
+/// ```
+/// # use intfic::parse_file::parse_str;
+/// let blocks = parse_str("inline.txt", ":- start\nHello!\n-> start").unwrap();
+/// 
+/// assert_eq!(blocks[0].name, String::from("start"));
+/// assert_eq!(blocks[0].choices()[0].result, String::from("start"));
+/// ```
+pub fn parse_str(filename: &str, contents: &str) -> Result<Vec<StoryBlock>, ParseError> {
+    let lines: Vec<String> = contents.lines().map(String::from).collect();
+    parse_lines(filename, &lines)
+}
 
-        let mut blocks: Vec<StoryBlock> = Vec::new();
-        let mut current_block: StoryBlock = StoryBlock::default();
-        let mut seen_block = false;
+/// Parses the contents of a story file from any buffered reader, such as a file, socket, or byte slice.
+/// 
+/// The filename is only used to describe where any problem was found.
+/// 
+/// ```
+/// # use intfic::parse_file::parse_reader;
+/// let contents: &[u8] = b":- start\nHello!";
+/// let blocks = parse_reader("inline.txt", contents).unwrap();
+/// 
+/// assert_eq!(blocks[0].text(), vec!["Hello!"]);
+/// ```
+pub fn parse_reader<R: BufRead>(filename: &str, reader: R) -> Result<Vec<StoryBlock>, ParseError> {
+    let lines: Vec<String> = reader.lines().collect::<io::Result<_>>().map_err(|e| ParseError::Io {
+        filename: String::from(filename),
+        message: e.to_string(),
+    })?;
+    parse_lines(filename, &lines)
+}
 
-        for line in lines {
-            if let Ok(text) = line {
-                parse_line(text, &mut blocks, &mut current_block, &mut seen_block)
-            }
+/// Parses the lines of a story file into a list of StoryBlocks, returning a ParseError if any line is malformed.
+/// 
+/// The filename is only used to describe where any problem was found.
+/// 
+/// ```
+/// # use intfic::parse_file::parse_lines;
+/// let lines: Vec<String> = vec![String::from(":- start"), String::from("Hello!")];
+/// let blocks = parse_lines("inline.txt", &lines).unwrap();
+/// 
+/// assert_eq!(blocks[0].name, String::from("start"));
+/// assert_eq!(blocks[0].text(), vec!["Hello!"]);
+/// ```
+pub fn parse_lines(filename: &str, lines: &[String]) -> Result<Vec<StoryBlock>, ParseError> {
+    let mut blocks: Vec<StoryBlock> = Vec::new();
+    let mut current_block: StoryBlock = StoryBlock::default();
+    let mut seen_block = false;
+    let mut sections: Vec<OpenSection> = Vec::new();
+
+    for (num, text) in lines.iter().enumerate() {
+        if let Err((offset, message)) = parse_line(text, num + 1, &mut blocks, &mut current_block, &mut seen_block, &mut sections) {
+            return Err(ParseError::Syntax {
+                filename: String::from(filename),
+                line: num + 1,
+                column: text.get(..offset).unwrap_or(text).chars().count() + 1,
+                text: text.clone(),
+                message,
+            });
         }
+    }
 
-        blocks.push(current_block);
-        Some(blocks)
-    } else {
-        println!("Error getting file: {}", filename);
-        None
+    if let Some(open) = sections.last() {
+        return Err(ParseError::Syntax {
+            filename: String::from(filename),
+            line: open.line,
+            column: 1,
+            text: lines[open.line - 1].clone(),
+            message: format!("`{}` is never closed with `{}`", open.keyword, open.closer()),
+        });
     }
-}
 
-// Gathers the text content of a file and saves it as a list of lines if successful.
-//
-// Story files should be placed in /resources to be found by this function.
-fn get_file(filename: &str) -> io::Result<io::Lines<io::BufReader<File>>> {
-    let resources: &Path = Path::new("resources");
-    let file = File::open(resources.join(filename))?;
-    Ok(io::BufReader::new(file).lines())
+    blocks.push(current_block);
+    Ok(blocks)
 }
 
 // Parses each line of the story file and constructs blocks that can be stored in out Vec<StoryBlock>
 //
 // Full Story File markup specification can be found above.
 fn parse_line(
-    text: String,
+    text: &str,
+    num: usize,
     blocks: &mut Vec<StoryBlock>,
     current_block: &mut StoryBlock,
     seen_block: &mut bool,
-) {
-    if text.len() > 1 {
-        let start: &str = &text[0..2];
-
-        match start {
-            ":-" => { // Start of a new block, so the end of the current one!
-                if *seen_block {
-                    blocks.push((*current_block).clone());
+    sections: &mut Vec<OpenSection>,
+) -> LineResult {
+    let keyword: &str = text.split(' ').next().unwrap_or_default();
+    if SECTION_KEYWORDS.contains(&keyword) {
+        return parse_section(text, keyword, num, current_block, sections);
+    }
+
+    match text.get(0..2).unwrap_or_default() {
+        ":-" => { // Start of a new block, so the end of the current one!
+            let name: &str = directive(text, ":-", "a block name")?;
+            if let Some(open) = sections.last() {
+                return Err((0, format!("expected `{}` to close the `{}` on line {} before the next block", open.closer(), open.keyword, open.line)));
+            }
+
+            if *seen_block {
+                blocks.push((*current_block).clone());
+            } else {
+                *seen_block = true;
+            }
+
+            *current_block = StoryBlock::new(String::from(name));
+        },
+        "=-" | "+-" => { // Set a flag or update a counter in the GameState
+            body(current_block, sections)?.push(effect(text)?);
+        },
+        "$-" => { // Ask the player for a string to store in the GameState
+            let content: &str = directive(text, "$-", "a string name")?;
+            let mut prompt_split = content.split(" => ");
+            let var_name: &str = prompt_split.next().unwrap_or_default();
+
+            let question: &str = match prompt_split.next() {
+                Some(question) if !var_name.contains(' ') && !question.trim().is_empty() => question,
+                _ => return Err((text.len(), String::from("expected a prompt like `$- name => question the player sees => rules`"))),
+            };
+
+            placeholders(text, question)?;
+
+            let mut prompt = Prompt {
+                variable: String::from(var_name),
+                question: String::from(question),
+                ..Prompt::default()
+            };
+
+            for rule in prompt_split.next().unwrap_or_default().split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+                if rule == "nonempty" {
+                    prompt.nonempty = true;
+                } else if let Some(max_len) = rule.strip_prefix("max ") {
+                    prompt.max_len = Some(max_len.trim().parse().map_err(|_| {
+                        (offset(text, max_len), format!("expected a whole number after `max`, found `{}`", max_len.trim()))
+                    })?);
                 } else {
-                    *seen_block = true;
+                    return Err((offset(text, rule), format!("expected `nonempty` or `max` and a number, found `{}`", rule)));
                 }
-        
-                *current_block = StoryBlock::new(read!(":- {}\n", text.bytes()));
-            },
-            "=-" => { // Set a flag in the GameState
-                let mut var_split: Vec<&str> = text.split(" = ").collect();
-                let var_name: String = read!("=- {}\n", var_split[0].bytes());
-                let var_value: bool = (var_split[1]).parse().unwrap();
+            }
 
-                current_block.flags.insert(var_name, var_value);
-            },
-            "+-" => { // Update a counter in the GameState
-                let mut var_split: Vec<&str> = text.split(" + ").collect();
-                let var_name: String = read!("+- {}\n", var_split[0].bytes());
-                let var_value: i32 = (var_split[1]).parse().unwrap();
+            body(current_block, sections)?.push(Statement::Prompt(prompt));
+        },
+        "*-" => { // New choice
+            let choice: &str = directive(text, "*-", "an option")?;
+            let choice_split: Vec<&str> = choice.split(" -> ").collect();
 
-                current_block.counters.insert(var_name, var_value);
-            },
-            "*-" => { // New choice
-                let mut choice_split: Vec<&str> = text.split(" -> ").collect();
-                let new_choice = Choice {
-                    text: read!("*- {}\n", choice_split[0].bytes()),
-                    typed: String::from(choice_split[1]),
-                    result: String::from(choice_split[2]),
-                };
+            if choice_split.len() < 3 {
+                return Err((
+                    text.len(),
+                    String::from("expected an option like `*- text the player sees -> strings to match -> result`"),
+                ));
+            }
 
-                current_block.options.push(new_choice);
-            },
-            "->" => { // No choice, just proceed to indicated block/file
-                let new_choice = Choice {
-                    text: String::default(),
-                    typed: String::default(),
-                    result: read!("-> {}\n", text.bytes()),
-                };
-        
-                current_block.options.push(new_choice);
-            },
-            _ => { // Just normal text
-                current_block.text.push(text);
-            },
+            if let Some(extra) = choice_split.get(4) {
+                return Err((offset(text, extra), String::from("expected nothing after the option's effects, use `;` to separate effects")));
+            }
+
+            let mut effects: Vec<Statement> = Vec::new();
+            for part in choice_split.get(3).map_or(Vec::new(), |segment| segment.split(';').map(str::trim).collect()) {
+                if !part.starts_with("=-") && !part.starts_with("+-") {
+                    return Err((offset(text, part), format!("expected an effect like `=- flag = true` or `+- counter + 1`, found `{}`", part)));
+                }
+                effects.push(effect(part).map_err(|(at, message)| (offset(text, part) + at, message))?);
+            }
+
+            let (kind, mut shown) = ChoiceKind::split_marker(choice_split[0]);
+            let mut shown_condition: Option<Expr> = None;
+            if shown.starts_with("?-") || shown.starts_with("#-") {
+                let (cond, expr) = condition(text, shown)?;
+                shown = shown[offset(shown, cond) + cond.len()..].split(" => ").nth(1).unwrap_or_default();
+                shown_condition = Some(expr);
+            }
+            placeholders(text, shown)?;
+
+            let new_choice = Choice {
+                text: String::from(shown),
+                condition: shown_condition,
+                typed: String::from(choice_split[1]),
+                result: String::from(choice_split[2]),
+                result_condition: target(text, choice_split[2])?,
+                kind,
+                index: written(current_block, sections, |statement| matches!(statement, Statement::Choice(_))),
+                effects,
+            };
+
+            body(current_block, sections)?.push(Statement::Choice(new_choice));
+        },
+        "?-" | "#-" => { // Conditional text, shown or not depending on its condition
+            let (cond, expr) = condition(text, text)?;
+            placeholders(text, text)?;
+
+            let mut lines = text[offset(text, cond) + cond.len()..].split(" => ").skip(1);
+            let conditional = Conditional {
+                condition: expr,
+                then: String::from(lines.next().unwrap_or_default()),
+                otherwise: lines.next().map(String::from),
+            };
+            body(current_block, sections)?.push(Statement::Conditional(conditional));
+        },
+        "~-" => { // A sequence of lines that take turns being shown
+            let content: &str = directive(text, "~-", "a kind of sequence")?;
+            let mut sequence_split = content.split(" => ");
+            let kind_name: &str = sequence_split.next().unwrap_or_default();
+
+            let kind: SequenceKind = match kind_name {
+                "stopping" => SequenceKind::Stopping,
+                "cycle" => SequenceKind::Cycle,
+                "shuffle" => SequenceKind::Shuffle,
+                "once" => SequenceKind::Once,
+                _ => {
+                    return Err((offset(text, kind_name), format!("expected `stopping`, `cycle`, `shuffle`, or `once`, found `{}`", kind_name)));
+                }
+            };
+
+            let mut lines: Vec<String> = Vec::new();
+            for line in sequence_split {
+                if line.trim().is_empty() {
+                    return Err((offset(text, line), String::from("expected a line of text between ` => `")));
+                } else if line.starts_with("?-") || line.starts_with("#-") {
+                    return Err((offset(text, line), String::from("lines of a sequence can't have conditions, put the sequence in a section instead")));
+                }
+                placeholders(text, line)?;
+                lines.push(String::from(line));
+            }
+            if lines.is_empty() {
+                return Err((text.len(), String::from("expected a sequence like `~- stopping => first line => second line`")));
+            }
+
+            let index: usize = written(current_block, sections, |statement| matches!(statement, Statement::Sequence(_)));
+            body(current_block, sections)?.push(Statement::Sequence(Sequence { kind, lines, index }));
+        },
+        "->" => { // No choice, just proceed to indicated block/file
+            let result: &str = directive(text, "->", "a block or file name")?;
+            let new_choice = Choice {
+                text: String::default(),
+                condition: None,
+                typed: String::default(),
+                result: String::from(result),
+                result_condition: target(text, result)?,
+                kind: ChoiceKind::Sticky,
+                index: written(current_block, sections, |statement| matches!(statement, Statement::Choice(_))),
+                effects: Vec::new(),
+            };
+
+            body(current_block, sections)?.push(Statement::Choice(new_choice));
+        },
+        _ => { // Just normal text
+            placeholders(text, text)?;
+            match body(current_block, sections) {
+                Ok(body) => body.push(Statement::Text(String::from(text))),
+                Err(_) if text.trim().is_empty() => (),
+                Err(e) => return Err(e),
+            }
+        },
+    }
+
+    Ok(())
+}
+
+// Parses a line that sets a flag like "=- name = value" or updates a counter like "+- name OP value".
+fn effect(text: &str) -> Result<Statement, (usize, String)> {
+    if text.starts_with("=-") {
+        let (var_name, var_value) = assignment(text, "=-", " = ", "flag")?;
+        let var_value: bool = var_value.parse().map_err(|_| {
+            (text.len() - var_value.len(), format!("expected `true` or `false`, found `{}`", var_value))
+        })?;
+
+        return Ok(Statement::SetFlag(String::from(var_name), var_value));
+    }
+
+    let content: &str = directive(text, "+-", "a counter name")?;
+    let (var_name, rest) = content.split_at(content.find(' ').unwrap_or(content.len()));
+    if var_name.is_empty() {
+        return Err((offset(text, content), String::from("expected a counter name after `+- `")));
+    }
+
+    let update: fn(Expr) -> Update = match rest.get(..3) {
+        Some(" + ") => Update::Add,
+        Some(" - ") => Update::Subtract,
+        Some(" * ") => Update::Multiply,
+        Some(" = ") => Update::Set,
+        _ => return Err((offset(text, rest), String::from("expected `+`, `-`, `*`, or `=` between the counter name and its value"))),
+    };
+    let var_value: &str = &rest[3..];
+    let var_value: Expr = Expr::parse(var_value).map_err(|e| (offset(text, var_value) + e.offset, e.message))?;
+
+    Ok(Statement::Counter(String::from(var_name), update(var_value)))
+}
+
+// Opens, continues, or closes a conditional section, depending on the keyword the line starts with.
+fn parse_section(
+    text: &str,
+    keyword: &str,
+    num: usize,
+    current_block: &mut StoryBlock,
+    sections: &mut Vec<OpenSection>,
+) -> LineResult {
+    let (opener, fallback) = match keyword {
+        "if-" | "elif-" | "else-" | "endif-" => ("if-", "else-"),
+        _ => ("switch-", "default-"),
+    };
+
+    if keyword == opener {
+        body(current_block, sections)?;
+        let content: &str = directive(text, opener, if opener == "if-" { "a condition" } else { "a value" })?;
+        let value: Expr = expression(text, content)?;
+        let (subject, branches) = match opener {
+            "if-" => (None, vec![(value, Vec::new())]),
+            _ => (Some(value), Vec::new()),
+        };
+
+        sections.push(OpenSection {
+            line: num,
+            keyword: opener,
+            subject,
+            section: Section { branches, otherwise: Vec::new() },
+            otherwise: false,
+        });
+        return Ok(());
+    }
+
+    let open: &mut OpenSection = match sections.last_mut() {
+        Some(open) if open.keyword == opener => open,
+        Some(open) => {
+            return Err((0, format!("expected `{}` to close the `{}` on line {}, found `{}`", open.closer(), open.keyword, open.line, keyword)));
+        }
+        None => return Err((0, format!("`{}` without an open `{}`", keyword, opener))),
+    };
+
+    if keyword == "elif-" || keyword == "case-" || keyword == fallback {
+        if open.otherwise {
+            return Err((0, format!("`{}` can't come after `{}`", keyword, fallback)));
+        }
+    } else if text.trim_end() != keyword {
+        return Err((keyword.len(), format!("expected nothing after `{}`", keyword)));
+    }
+
+    match keyword {
+        "elif-" => {
+            let condition: Expr = expression(text, directive(text, keyword, "a condition")?)?;
+            open.section.branches.push((condition, Vec::new()));
+        }
+        "case-" => {
+            let content: &str = directive(text, keyword, "a value")?;
+            let subject: Expr = open.subject.clone().unwrap_or(Expr::Int(0));
+            let mut condition: Option<Expr> = None;
+
+            for value in content.split(',').map(str::trim) {
+                let matches = Expr::Binary(Box::new(subject.clone()), Op::Eq, Box::new(expression(text, value)?));
+                condition = Some(match condition {
+                    Some(previous) => Expr::Binary(Box::new(previous), Op::Or, Box::new(matches)),
+                    None => matches,
+                });
+            }
+            open.section.branches.push((condition.unwrap_or(Expr::Bool(false)), Vec::new()));
         }
-    } else { // Just normal text
-        current_block.text.push(text);
+        "else-" | "default-" => {
+            if text.trim_end() != keyword {
+                return Err((keyword.len(), format!("expected nothing after `{}`", keyword)));
+            }
+            open.otherwise = true;
+        }
+        _ => { // The end of the section, which becomes a statement in whatever it was opened in
+            let open: OpenSection = sections.pop().expect("the section being closed is open");
+            body(current_block, sections)?.push(Statement::Section(open.section));
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the statements new lines are added to: the current branch of the innermost open section, or else the block's body.
+fn body<'a>(current_block: &'a mut StoryBlock, sections: &'a mut [OpenSection]) -> Result<&'a mut Vec<Statement>, (usize, String)> {
+    match sections.last_mut() {
+        Some(open) if open.otherwise => Ok(&mut open.section.otherwise),
+        Some(open) => match open.section.branches.last_mut() {
+            Some((_, statements)) => Ok(statements),
+            None => Err((0, format!("expected `case-` or `default-` after the `switch-` on line {}", open.line))),
+        },
+        None => Ok(&mut current_block.body),
+    }
+}
+
+// Counts the statements of the current block written so far that are of the given kind, including those in sections that are still open.
+//
+// Sequences and options are told apart by the order they're written in, so this is the index of the next one.
+fn written(current_block: &StoryBlock, sections: &[OpenSection], kind: fn(&Statement) -> bool) -> usize {
+    count(&current_block.body, kind) + sections.iter().map(|open| count_section(&open.section, kind)).sum::<usize>()
+}
+
+// Counts the given statements of the given kind, including those in every branch of their sections.
+fn count(statements: &[Statement], kind: fn(&Statement) -> bool) -> usize {
+    statements
+        .iter()
+        .map(|statement| match statement {
+            Statement::Section(section) => count_section(section, kind),
+            statement if kind(statement) => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+// Counts the statements of the given kind in every branch of a section.
+fn count_section(section: &Section, kind: fn(&Statement) -> bool) -> usize {
+    section.branches.iter().map(|(_, statements)| count(statements, kind)).sum::<usize>() + count(&section.otherwise, kind)
+}
+
+// Parses part of a line as an Expr, pointing any problem found at the right place within the line.
+//
+// The part must be a slice of the given line.
+fn expression(text: &str, part: &str) -> Result<Expr, (usize, String)> {
+    Expr::parse(part).map_err(|e| (offset(text, part) + e.offset, e.message))
+}
+
+// Returns the non-empty content of a line after the given prefix and the space following it.
+fn directive<'a>(text: &'a str, prefix: &str, expected: &str) -> Result<&'a str, (usize, String)> {
+    match text[prefix.len()..].strip_prefix(' ') {
+        Some(content) if !content.trim().is_empty() => Ok(content),
+        Some(_) => Err((prefix.len() + 1, format!("expected {} after `{} `", expected, prefix))),
+        None => Err((prefix.len(), format!("expected a space after `{}`", prefix))),
+    }
+}
+
+// Checks that a conditional like "?- condition => text" has a well formed condition, and returns the condition as written and as parsed.
+fn condition<'a>(text: &str, conditional: &'a str) -> Result<(&'a str, Expr), (usize, String)> {
+    let start: usize = offset(text, conditional);
+    let content: &str = directive(conditional, &conditional[..2], "a condition").map_err(|(at, message)| (start + at, message))?;
+
+    let cond: &str = match content.find(" => ") {
+        Some(split) => &content[..split],
+        None => return Err((start + conditional.len(), String::from("expected ` => ` and a line of text after the condition"))),
+    };
+
+    let expr: Expr = Expr::parse(cond).map_err(|e| (offset(text, cond) + e.offset, e.message))?;
+    Ok((cond, expr))
+}
+
+// Checks that the `{name}` placeholders in part of a line are well formed. Whether their variables exist is only known as the story is played.
+fn placeholders(text: &str, part: &str) -> Result<(), (usize, String)> {
+    template::variables(part).map_err(|e| (offset(text, part) + e.offset, e.message))?;
+    Ok(())
+}
+
+// Describes why the given variable can't be changed or read as the given kind, if the given declarations don't allow it.
+fn undeclared(variables: &HashMap<String, Variable>, name: &str, kind: &str, used: &str) -> Option<String> {
+    match variables.get(name) {
+        Some(variable) if variable.kind() == kind => None,
+        Some(variable) => Some(format!("`{}` is declared as a {}, but is {} here as a {}", name, variable.kind(), used, kind)),
+        None => Some(format!("{} `{}` isn't declared in the variables of {}", kind, name, Manifest::FILENAME)),
+    }
+}
+
+// Finds the first line of the given block that changes the given variable with the given prefix,
+// and the byte offset of its name in that line, so a variable the Loader doesn't declare can be pointed at.
+// Falls back to the block's title.
+fn variable_line(lines: &[String], block: &str, prefix: &str, name: &str) -> (usize, usize) {
+    let title: usize = lines.iter().position(|line| line.strip_prefix(":- ") == Some(block)).unwrap_or(0);
+    let written: String = format!("{} {}", prefix, name);
+
+    lines
+        .iter()
+        .enumerate()
+        .skip(title)
+        .find_map(|(num, text)| {
+            let start: usize = match text.get(0..2).unwrap_or_default() {
+                "*-" => text.split(" -> ").nth(3).map_or(text.len(), |effects| offset(text, effects)),
+                _ => 0,
+            };
+            word(text, start, &written).map(|at| (num, at + prefix.len() + 1))
+        })
+        .unwrap_or((title, 0))
+}
+
+// Returns the byte offset of the first place the given name is written as a whole word in a line, at or after the given offset.
+fn word(text: &str, from: usize, name: &str) -> Option<usize> {
+    let is_name = |c: char| c.is_alphanumeric() || c == '_';
+
+    text.get(from..)?
+        .match_indices(name)
+        .map(|(at, _)| from + at)
+        .find(|at| !text[..*at].ends_with(is_name) && !text[*at + name.len()..].starts_with(is_name))
+}
+
+// Returns the byte offset of part of a line within it, so problems found in the part can be pointed at within the line.
+//
+// The part must be a slice of the given line.
+fn offset(text: &str, part: &str) -> usize {
+    part.as_ptr() as usize - text.as_ptr() as usize
+}
+
+// Checks that a result leading to a block in another story file looks like "file.txt#block".
+//
+// A result may also pick between two targets with a condition like "?- condition => target => target if it fails",
+// in which case the parsed condition is returned.
+fn target(text: &str, result: &str) -> Result<Option<Expr>, (usize, String)> {
+    if result.starts_with("?-") || result.starts_with("#-") {
+        let (cond, expr) = condition(text, result)?;
+        let mut target_split = result[offset(result, cond) + cond.len()..].split(" => ").skip(1);
+
+        return match (target_split.next(), target_split.next(), target_split.next()) {
+            (Some(then), Some(otherwise), None) if !then.trim().is_empty() && !otherwise.trim().is_empty() => {
+                target(text, then)?;
+                target(text, otherwise)?;
+                Ok(Some(expr))
+            }
+            _ => Err((
+                offset(text, result) + result.len(),
+                String::from("expected a result like `?- condition => target => target if the condition fails`"),
+            )),
+        };
+    }
+
+    if let Some(split) = result.find('#') {
+        let offset: usize = offset(text, result) + split;
+
+        if !result[..split].ends_with(".txt") {
+            return Err((offset, String::from("expected a story file like `chapter2.txt` before `#`")));
+        } else if result[split + 1..].trim().is_empty() {
+            return Err((offset + 1, String::from("expected a block name after `#`")));
+        }
+    }
+
+    Ok(None)
+}
+
+// Splits a line like "=- name = value" into its variable name and value.
+fn assignment<'a>(text: &'a str, prefix: &str, operator: &str, kind: &str) -> Result<(&'a str, &'a str), (usize, String)> {
+    let content: &str = directive(text, prefix, &format!("a {} name", kind))?;
+
+    match content.find(operator) {
+        Some(0) => Err((prefix.len() + 1, format!("expected a {} name before `{}`", kind, operator.trim()))),
+        Some(split) => Ok((&content[..split], &content[split + operator.len()..])),
+        None => Err((
+            text.len(),
+            format!("expected `{}` between the {} name and its value", operator.trim(), kind),
+        )),
     }
 }
@@ -1,8 +1,9 @@
-use text_io::read;
-
+use crate::frontend::Frontend;
 use crate::game_state::GameState;
 use crate::print_debug;
-use crate::write_out::{type_text, Color};
+use crate::save::{SaveError, SlotInfo};
+use crate::story_block::Prompt;
+use crate::write_out::Color;
 
 /// Yes, No, or Unsure. Corresponds with a dictionary of responses that indicate one of these three answers.
 #[derive(Debug, PartialEq)]
@@ -179,7 +180,7 @@ const RETURNS: &[&str] = &[
     "run away",
 ];
 
-// Dictionary for game.quit()
+// Dictionary for quitting the game
 const EXITS: &[&str] = &[
     "exit",
     "exit game",
@@ -246,78 +247,180 @@ pub fn sanitize(input: String) -> String {
         .to_lowercase()
 }
 
-/// Gets input from the user and checks if it matches a keyword (returns None) or else returns Some(String).
+/// Gets input from the user through the given Frontend and checks if it matches a keyword (returns None) or else returns Some(String).
 /// 
-/// Keywords are the following:
-/// * exit - asks to save if you haven't recently, then quits the game. See [game_state::GameState::quit()](../game_state/struct.GameState.html#method.quit)
-/// * save - saves the game. See [game_state::GameState::save()](../game_state/struct.GameState.html#method.save)
-/// * load - loads the game. See [game_state::GameState::load()](../game_state/struct.GameState.html#method.load)
+/// Also returns None if the Frontend has no more input to give.
+/// See [check_keywords()](fn.check_keywords.html) for the list of keywords.
 /// 
 /// ```no_run
+/// # use intfic::frontend::Terminal;
 /// # use intfic::parse_input::get_input;
 /// # use intfic::game_state::GameState;
 /// let mut game: GameState = GameState::new("Test GameState");
 /// 
-/// assert_eq!(get_input(&mut game), Some(String::from("open door"))); // If the user typed "open door"
-/// assert_eq!(get_input(&mut game), None); // If the user typed "load" or "save"
+/// assert_eq!(get_input(&mut game, &mut Terminal), Some(String::from("open door"))); // If the user typed "open door"
+/// assert_eq!(get_input(&mut game, &mut Terminal), None); // If the user typed "load" or "save"
 /// ```
-pub fn get_input(game: &mut GameState) -> Option<String> {
-    let input: String = sanitize(read!("{}\n"));
+pub fn get_input(game: &mut GameState, frontend: &mut dyn Frontend) -> Option<String> {
+    let input: String = sanitize(frontend.read_input()?);
+    check_keywords(input, game, frontend)
+}
 
+/// Checks if sanitized input matches a keyword and handles it (returns None), or else returns Some(String).
+/// 
+/// Keywords are the following:
+/// * exit - asks to save if you haven't recently, then quits the game, unless the save fails. Quitting sets
+///   [game_state::GameState::quitting](../game_state/struct.GameState.html#structfield.quitting) rather than ending the program,
+///   so whatever is hosting the game can decide what to do.
+/// * save - lists the game's save slots and asks which one to save to, by number or by a new name.
+///   See [game_state::GameState::save_slot()](../game_state/struct.GameState.html#method.save_slot)
+/// * load - lists the game's save slots and asks which one to load, or to delete by typing "delete" and its number.
+///   See [game_state::GameState::load_slot()](../game_state/struct.GameState.html#method.load_slot)
+/// 
+/// ```
+/// # use intfic::frontend::Mock;
+/// # use intfic::parse_input::check_keywords;
+/// # use intfic::game_state::GameState;
+/// let mut game: GameState = GameState::new("Test GameState");
+/// 
+/// assert_eq!(check_keywords(String::from("open door"), &mut game, &mut Mock::new(&[])), Some(String::from("open door")));
+/// ```
+pub fn check_keywords(input: String, game: &mut GameState, frontend: &mut dyn Frontend) -> Option<String> {
     if EXITS.contains(&&input[..]) {
-        if game.get_flag("saved") {
-            game.quit();
-            None
-        } else {
-            match ask_question("Do you want to save first?", game) {
-                Some(Answer::Yes) => {
-                    game.save();
-                    game.quit();
-                    None
-                }
-                Some(Answer::No) => {
-                    game.quit();
-                    None
-                }
+        let ready: bool = game.get_flag("saved")
+            || match ask_question("Do you want to save first?", game, frontend) {
+                Some(Answer::Yes) => report(game.save(), "Game Saved!", frontend),
+                Some(Answer::No) => true,
                 Some(Answer::Unsure) => {
-                    type_text("I'll just save for you...", Color::White, false);
-                    game.save();
-                    game.quit();
-                    None
+                    frontend.write_line("I'll just save for you...", Color::White);
+                    report(game.save(), "Game Saved!", frontend)
                 }
-                _ => None,
-            }
+                None => false,
+            };
+
+        if ready {
+            frontend.write_line("See you next time!", Color::White);
+            game.quitting = true;
         }
+        None
     } else if SAVES.contains(&&input[..]) {
-        game.save();
-        game.start();
+        pick_save_slot(game, frontend);
         None
     } else if LOADS.contains(&&input[..]) {
-        game.load();
-        game.start();
+        pick_load_slot(game, frontend);
         None
     } else {
         Some(input)
     }
 }
 
+// Lists the game's save slots and asks the player which one to save to. A number picks a listed slot, and anything else names a new one.
+fn pick_save_slot(game: &mut GameState, frontend: &mut dyn Frontend) {
+    let slots: Vec<SlotInfo> = list_slots(game, frontend);
+    frontend.prompt("Save to which slot? Type its number or a new name, or nothing to cancel.");
+
+    match frontend.read_input().map(|input| String::from(input.trim())) {
+        Some(input) if !input.is_empty() => {
+            let result = game.save_slot(&slot_name(&input, &slots));
+            report(result, "Game Saved!", frontend);
+        }
+        _ => frontend.write_line("Nothing was saved.", Color::White),
+    }
+}
+
+// Lists the game's save slots and asks the player which one to load, or to delete if they type "delete" before it.
+fn pick_load_slot(game: &mut GameState, frontend: &mut dyn Frontend) {
+    let slots: Vec<SlotInfo> = list_slots(game, frontend);
+    if slots.is_empty() {
+        return frontend.write_line("No save data found", Color::Red);
+    }
+    frontend.prompt("Load which slot? Type its number or name, \"delete\" and its number to delete it, or nothing to cancel.");
+
+    match frontend.read_input().map(|input| String::from(input.trim())) {
+        Some(input) if input.is_empty() => frontend.write_line("Nothing was loaded.", Color::White),
+        Some(input) => match strip_delete(&input) {
+            Some(slot) => {
+                let result = game.delete_slot(&slot_name(slot, &slots));
+                report(result, "Save Deleted!", frontend);
+            }
+            None => {
+                let result = game.load_slot(&slot_name(&input, &slots));
+                report(result, "Game Loaded!", frontend);
+            }
+        },
+        None => (),
+    }
+}
+
+// Writes out the game's save slots, numbered from 1, and returns them. If they can't be listed, says why and returns none.
+fn list_slots(game: &GameState, frontend: &mut dyn Frontend) -> Vec<SlotInfo> {
+    let slots: Vec<SlotInfo> = match game.slots() {
+        Ok(slots) => slots,
+        Err(e) => {
+            frontend.write_line(&e.to_string(), Color::Red);
+            Vec::new()
+        }
+    };
+
+    for (num, info) in slots.iter().enumerate() {
+        frontend.write_line(&format!("{}) {}", num + 1, info), Color::White);
+    }
+    slots
+}
+
+// Tells the player whether a save, load, or delete worked, and returns true if it did.
+fn report(result: Result<(), SaveError>, success: &str, frontend: &mut dyn Frontend) -> bool {
+    match result {
+        Ok(()) => {
+            frontend.write_line(success, Color::White);
+            true
+        }
+        Err(e) => {
+            frontend.write_line(&e.to_string(), Color::Red);
+            false
+        }
+    }
+}
+
+// Returns the name of the slot the player picked, either by its number in the list or by typing its name.
+// A typed name picks the listed slot it matches, ignoring case, and otherwise is used exactly as it was typed.
+fn slot_name(input: &str, slots: &[SlotInfo]) -> String {
+    match input.parse::<usize>() {
+        Ok(num) if (1..=slots.len()).contains(&num) => slots[num - 1].slot.clone(),
+        _ => slots
+            .iter()
+            .find(|info| info.slot == input)
+            .or_else(|| slots.iter().find(|info| info.slot.to_lowercase() == input.to_lowercase()))
+            .map_or_else(|| String::from(input), |info| info.slot.clone()),
+    }
+}
+
+// Returns the slot the player typed after "delete", in any case, if they asked to delete one.
+fn strip_delete(input: &str) -> Option<&str> {
+    match input.split_once(char::is_whitespace) {
+        Some((keyword, slot)) if keyword.eq_ignore_ascii_case("delete") => Some(slot.trim()),
+        _ => None,
+    }
+}
+
 /// Asks a given yes-no question and returns Some(Answer) if the user doesn't type a keyword.
 /// 
 /// If the user types something that does not correspond to any of the Answer dictionaries, 
 /// the question will repeat until a proper response or keyword is given.
 /// 
 /// ```no_run
+/// # use intfic::frontend::Terminal;
 /// # use intfic::parse_input::{ask_question, Answer};
 /// # use intfic::game_state::GameState;
 /// let mut game: GameState = GameState::new("Test GameState");
 /// 
-/// assert_eq!(ask_question("Continue?", &mut game), Some(Answer::Yes)); // If the user typed "y"
-/// assert_eq!(ask_question("Continue?", &mut game), None); // If the user typed "load" or "save"
+/// assert_eq!(ask_question("Continue?", &mut game, &mut Terminal), Some(Answer::Yes)); // If the user typed "y"
+/// assert_eq!(ask_question("Continue?", &mut game, &mut Terminal), None); // If the user typed "load" or "save"
 /// ```
-pub fn ask_question(question: &str, game: &mut GameState) -> Option<Answer> {
+pub fn ask_question(question: &str, game: &mut GameState, frontend: &mut dyn Frontend) -> Option<Answer> {
     loop {
-        type_text(question, Color::Cyan, true);
-        if let Some(input) = get_input(game) {
+        frontend.prompt(question);
+        if let Some(input) = get_input(game, frontend) {
             if input.is_empty() {
                 continue;
             }
@@ -326,13 +429,49 @@ pub fn ask_question(question: &str, game: &mut GameState) -> Option<Answer> {
                 return Some(answer);
             }
 
-            type_text("I didn't understand that.", Color::White, false);
+            frontend.write_line("I didn't understand that.", Color::White);
         } else {
             return None;
         }
     }
 }
 
+/// Asks the player the given Prompt's question, stores their answer as a string in our GameState, and returns it.
+/// 
+/// The answer is taken as typed rather than sanitized or checked for keywords, so the player may answer "Save" if they like.
+/// If the answer doesn't follow the Prompt's rules, the question will repeat until it does.
+/// Returns None if the Frontend runs out of input first.
+/// 
+/// ```
+/// # use intfic::frontend::Mock;
+/// # use intfic::game_state::GameState;
+/// # use intfic::parse_input::ask_text;
+/// # use intfic::story_block::Prompt;
+/// let mut game: GameState = GameState::new("Test GameState");
+/// let prompt = Prompt {
+///     variable: String::from("player_name"),
+///     question: String::from("What's your name?"),
+///     nonempty: true,
+///     max_len: None,
+/// };
+/// 
+/// assert_eq!(ask_text(&prompt, &mut game, &mut Mock::new(&["", "Sam"])), Some(String::from("Sam")));
+/// assert_eq!(game.get_string("player_name"), "Sam");
+/// ```
+pub fn ask_text(prompt: &Prompt, game: &mut GameState, frontend: &mut dyn Frontend) -> Option<String> {
+    loop {
+        frontend.prompt(&prompt.filled_question(game));
+
+        match prompt.validate(&frontend.read_input()?) {
+            Ok(answer) => {
+                game.set_string(&prompt.variable, &answer);
+                return Some(answer);
+            }
+            Err(message) => frontend.write_line(&message, Color::Red),
+        }
+    }
+}
+
 // Searches Answer dictionaries for given input
 fn parse_answer(input: &str) -> Option<Answer> {
     if AFFIRMATIVES.contains(&input) {
@@ -356,17 +495,18 @@ fn parse_answer(input: &str) -> Option<Answer> {
 /// the question will repeat until a proper response or keyword is given.
 /// 
 /// ```no_run
+/// # use intfic::frontend::Terminal;
 /// # use intfic::parse_input::{ask_direction, Direction};
 /// # use intfic::game_state::GameState;
 /// let mut game: GameState = GameState::new("Test GameState");
 /// 
-/// assert_eq!(ask_direction("Which Way?", &mut game), Some(Direction::North)); // If the user typed "Northward!"
-/// assert_eq!(ask_direction("Which Way??", &mut game), None); // If the user typed "load" or "save"
+/// assert_eq!(ask_direction("Which Way?", &mut game, &mut Terminal), Some(Direction::North)); // If the user typed "Northward!"
+/// assert_eq!(ask_direction("Which Way??", &mut game, &mut Terminal), None); // If the user typed "load" or "save"
 /// ```
-pub fn ask_direction(question: &str, game: &mut GameState) -> Option<Direction> {
+pub fn ask_direction(question: &str, game: &mut GameState, frontend: &mut dyn Frontend) -> Option<Direction> {
     loop {
-        type_text(question, Color::Cyan, true);
-        if let Some(input) = get_input(game) {
+        frontend.prompt(question);
+        if let Some(input) = get_input(game, frontend) {
             if input.is_empty() {
                 continue;
             }
@@ -375,7 +515,7 @@ pub fn ask_direction(question: &str, game: &mut GameState) -> Option<Direction>
                 return Some(direction);
             }
 
-            type_text("I didn't understand that.", Color::White, false);
+            frontend.write_line("I didn't understand that.", Color::White);
         } else {
             return None;
         }
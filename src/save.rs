@@ -0,0 +1,380 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::game_state::GameState;
+
+/// The slot games are saved to and loaded from when the player doesn't pick one, such as when saving before quitting.
+pub const QUICKSAVE: &str = "quicksave";
+
+/// The environment variable that, when set, overrides the directory games are saved in. See [FileStore](../store/struct.FileStore.html)
+pub const SAVE_DIR_VAR: &str = "INTFIC_SAVE_DIR";
+
+/// The version of the save format written by this version of intfic.
+/// 
+/// Saves written in older versions are upgraded when they're loaded, but saves written in newer versions can't be loaded.
+/// 
+/// * 0 - the GameState on its own, or with a SlotInfo that has no version
+/// * 1 - the GameState with a SlotInfo describing it
+/// * 2 - adds the story's fingerprint to the SlotInfo, and the blocks to fall back to if the story changes to the GameState
+pub const SAVE_VERSION: u32 = 2;
+
+// The most characters of story text kept in a slot's excerpt.
+const EXCERPT_LEN: usize = 60;
+
+/// A description of a saved game, stored alongside it so the player can tell their saves apart.
+#[derive(Debug, Default, Clone, Deserialize, PartialEq, Serialize)]
+pub struct SlotInfo {
+    /// The name of the slot, such as "1" or "before the dragon".
+    pub slot: String,
+    /// When the game was saved, in seconds since the Unix epoch.
+    pub saved_at: u64,
+    /// The story file and block the game was saved in, like "example_1.txt#start".
+    pub block: String,
+    /// The last line of story text the player saw before saving, shortened if it's long.
+    pub excerpt: String,
+    /// How long the game had been played for when it was saved, in seconds.
+    pub playtime: u64,
+    /// The version of the save format the game was saved in, 0 for saves from before formats were versioned.
+    #[serde(default)]
+    pub version: u32,
+    /// A fingerprint of the story the game was being played with, 0 if it wasn't known.
+    /// See [Loader::fingerprint()](../parse_file/struct.Loader.html#method.fingerprint)
+    #[serde(default)]
+    pub story_hash: u64,
+}
+
+impl SlotInfo {
+    /// Describes the given GameState as it is now, to be saved in the given slot.
+    ///
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::SlotInfo;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("example_1.txt", "start");
+    /// game.excerpt = String::from("It's nearly pitch black out tonight.");
+    ///
+    /// let info: SlotInfo = SlotInfo::new("1", &game);
+    /// assert_eq!(info.block, String::from("example_1.txt#start"));
+    /// assert_eq!(info.excerpt, String::from("It's nearly pitch black out tonight."));
+    /// ```
+    pub fn new(slot: &str, game: &GameState) -> SlotInfo {
+        let excerpt: String = if game.excerpt.chars().count() > EXCERPT_LEN {
+            let cut: String = game.excerpt.chars().take(EXCERPT_LEN - 1).collect();
+            format!("{}…", cut.trim_end())
+        } else {
+            game.excerpt.clone()
+        };
+
+        SlotInfo {
+            slot: String::from(slot),
+            saved_at: now(),
+            block: game.progress_ref(),
+            excerpt,
+            playtime: game.playtime(),
+            version: SAVE_VERSION,
+            story_hash: game.story_hash,
+        }
+    }
+}
+
+impl fmt::Display for SlotInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (saved {}, played {}) at {}", self.slot, ago(self.saved_at, now()), duration(self.playtime), self.block)?;
+        if !self.excerpt.is_empty() {
+            write!(f, ": \"{}\"", self.excerpt)?;
+        }
+        Ok(())
+    }
+}
+
+/// Something that went wrong while saving, loading, listing, or deleting save slots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveError {
+    /// The local data directory saves are kept in couldn't be found.
+    NoDataDir,
+    /// A save file, or the directory it's kept in, couldn't be created, read, written, or removed.
+    Io {
+        /// The file or directory that couldn't be accessed.
+        path: PathBuf,
+        /// A description of what went wrong.
+        message: String,
+    },
+    /// The game couldn't be written out as a save.
+    Serialize {
+        /// A description of what went wrong.
+        message: String,
+    },
+    /// A save couldn't be read, most likely because it has been damaged or edited by hand.
+    Deserialize {
+        /// The name of the slot.
+        slot: String,
+        /// A description of what went wrong.
+        message: String,
+    },
+    /// There is no save in the slot.
+    Missing {
+        /// The name of the slot.
+        slot: String,
+    },
+    /// The save was written in a newer version of the save format than this version of intfic reads.
+    Version {
+        /// The name of the slot.
+        slot: String,
+        /// The version the save was written in.
+        found: u32,
+        /// The newest version this version of intfic reads, [SAVE_VERSION](constant.SAVE_VERSION.html).
+        expected: u32,
+    },
+    /// The slot would be kept in the same place as another slot, whose name only differs in ways that can't be used in a file name.
+    Conflict {
+        /// The name of the slot.
+        slot: String,
+        /// The name of the slot already kept there.
+        existing: String,
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::NoDataDir => write!(f, "Couldn't find a local data directory to keep saves in"),
+            SaveError::Io { path, message } => write!(f, "Couldn't access {}: {}", path.display(), message),
+            SaveError::Serialize { message } => write!(f, "Couldn't save the game: {}", message),
+            SaveError::Deserialize { slot, message } => write!(f, "The save in slot \"{}\" isn't valid: {}", slot, message),
+            SaveError::Missing { slot } => write!(f, "No save data found in slot \"{}\"", slot),
+            SaveError::Version { slot, found, expected } => write!(
+                f,
+                "The save in slot \"{}\" is in version {} of the save format, but only versions up to {} can be loaded",
+                slot, found, expected,
+            ),
+            SaveError::Conflict { slot, existing } => write!(
+                f,
+                "Slot \"{}\" would replace the save in slot \"{}\", please choose a different name",
+                slot, existing,
+            ),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+/// Brings saves made with an older version of a story up to date, renaming the blocks, flags, and counters that have changed since.
+/// 
+/// Give your [Engine](../engine/struct.Engine.html) its migrations, and they're applied whenever the player loads
+/// a save made with a different version of the story than the one being played. Renames of things a save doesn't have do nothing,
+/// so every migration a story has ever needed can be kept and applied to every old save.
+/// 
+/// ```
+/// # use intfic::game_state::GameState;
+/// # use intfic::save::Migration;
+/// let migration: Migration = Migration::new()
+///     .rename_block("example_1.txt#neat", "example_1.txt#cool")
+///     .rename_flag("has_key", "has_gate_key")
+///     .rename_counter("gold", "coins")
+///     .then(|game| game.set_flag("migrated", true));
+/// 
+/// let mut game: GameState = GameState::new("Test GameState");
+/// game.set_progress("example_1.txt", "neat");
+/// game.set_flag("has_key", true);
+/// game.set_counter("gold", 5);
+/// 
+/// migration.apply(&mut game);
+/// assert_eq!(game.progress_ref(), "example_1.txt#cool");
+/// assert_eq!(game.get_flag("has_gate_key"), true);
+/// assert_eq!(game.get_counter("coins"), 5);
+/// assert_eq!(game.get_flag("migrated"), true);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Migration {
+    blocks: Vec<(String, String)>,
+    flags: Vec<(String, String)>,
+    counters: Vec<(String, String)>,
+    hooks: Vec<fn(&mut GameState)>,
+}
+
+impl Migration {
+    /// Creates a Migration that doesn't change anything yet.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::Migration;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_flag("has_key", true);
+    /// Migration::new().apply(&mut game);
+    /// 
+    /// assert_eq!(game.get_flag("has_key"), true);
+    /// ```
+    pub fn new() -> Migration {
+        Migration::default()
+    }
+
+    /// Renames a block, given references like "example_1.txt#neat", along with how many times it, its sequences, and its options were read.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::Migration;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.visit("example_1.txt", "neat");
+    /// 
+    /// Migration::new().rename_block("example_1.txt#neat", "example_2.txt#neat").apply(&mut game);
+    /// assert_eq!(game.visits.get("example_2.txt#neat"), Some(&1));
+    /// ```
+    pub fn rename_block(mut self, from: &str, to: &str) -> Migration {
+        self.blocks.push((String::from(from), String::from(to)));
+        self
+    }
+
+    /// Renames a flag, keeping its value.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::Migration;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_flag("has_key", true);
+    /// 
+    /// Migration::new().rename_flag("has_key", "has_gate_key").apply(&mut game);
+    /// assert_eq!(game.get_flag("has_key"), false);
+    /// assert_eq!(game.get_flag("has_gate_key"), true);
+    /// ```
+    pub fn rename_flag(mut self, from: &str, to: &str) -> Migration {
+        self.flags.push((String::from(from), String::from(to)));
+        self
+    }
+
+    /// Renames a counter, keeping its value and limits.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::Migration;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_counter("gold", 5);
+    /// 
+    /// Migration::new().rename_counter("gold", "coins").apply(&mut game);
+    /// assert_eq!(game.get_counter("coins"), 5);
+    /// ```
+    pub fn rename_counter(mut self, from: &str, to: &str) -> Migration {
+        self.counters.push((String::from(from), String::from(to)));
+        self
+    }
+
+    /// Runs the given function on the GameState after the renames, for changes that aren't just renaming something.
+    /// 
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::Migration;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// 
+    /// Migration::new().then(|game| game.set_counter("health", 10)).apply(&mut game);
+    /// assert_eq!(game.get_counter("health"), 10);
+    /// ```
+    pub fn then(mut self, hook: fn(&mut GameState)) -> Migration {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Applies this Migration's renames to the given GameState in the order they were added, then runs its functions.
+    /// 
+    /// See [Migration](struct.Migration.html) for an example.
+    pub fn apply(&self, game: &mut GameState) {
+        for (from, to) in self.blocks.iter() {
+            if game.progress_ref() == *from {
+                game.set_progress_ref(to);
+            }
+            for checkpoint in game.checkpoints.iter_mut().filter(|checkpoint| *checkpoint == from) {
+                *checkpoint = to.clone();
+            }
+
+            rename(&mut game.visits, from, to);
+            rename_prefixed(&mut game.sequences, &format!("{}~", from), &format!("{}~", to));
+            rename_prefixed(&mut game.chosen, &format!("{}*", from), &format!("{}*", to));
+        }
+
+        for (from, to) in self.flags.iter() {
+            rename(&mut game.flags, from, to);
+        }
+        for (from, to) in self.counters.iter() {
+            rename(&mut game.counters, from, to);
+            rename(&mut game.limits, from, to);
+        }
+
+        for hook in self.hooks.iter() {
+            hook(game);
+        }
+    }
+}
+
+// Moves the values of every key of a map starting with one prefix to the same key starting with another.
+fn rename_prefixed<V>(map: &mut HashMap<String, V>, from: &str, to: &str) {
+    let keys: Vec<String> = map.keys().filter(|key| key.starts_with(from)).cloned().collect();
+    for key in keys {
+        rename(map, &key, &format!("{}{}", to, &key[from.len()..]));
+    }
+}
+
+// Moves the value of one key of a map to another, if it has one.
+fn rename<V>(map: &mut HashMap<String, V>, from: &str, to: &str) {
+    if let Some(value) = map.remove(from) {
+        map.insert(String::from(to), value);
+    }
+}
+
+/// Turns a game or slot name into a name that's safe to use as a file or directory on any platform.
+/// 
+/// Characters that aren't letters, numbers, spaces, `-`, `_`, or `.` are replaced with `_`,
+/// leading and trailing dots and spaces are trimmed, and names Windows reserves for devices are suffixed with `_`.
+/// 
+/// ```
+/// # use intfic::save::file_name;
+/// assert_eq!(file_name("before the dragon"), "before the dragon");
+/// assert_eq!(file_name("Act 1: The Road"), "Act 1_ The Road");
+/// assert_eq!(file_name("../../etc/passwd"), "_.._etc_passwd");
+/// assert_eq!(file_name("con"), "con_");
+/// assert_eq!(file_name(""), "_");
+/// ```
+pub fn file_name(name: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || " -_.".contains(c) { c } else { '_' })
+        .collect();
+    let safe: &str = safe.trim_matches(|c| c == '.' || c == ' ');
+
+    let stem: String = safe.split('.').next().unwrap_or_default().to_uppercase();
+    let reserved: bool = ["CON", "PRN", "AUX", "NUL"].contains(&stem.as_str())
+        || (stem.len() == 4 && (stem.starts_with("COM") || stem.starts_with("LPT")) && stem.ends_with(|c: char| c.is_ascii_digit()));
+
+    match safe {
+        "" => String::from("_"),
+        _ if reserved => format!("{}_", safe),
+        _ => String::from(safe),
+    }
+}
+
+// Returns the current time in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+// Describes a number of seconds played, like "1h 02m", "12m 03s", or "45s".
+fn duration(secs: u64) -> String {
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
+// Describes how long ago a time was, like "just now" or "5 minutes ago".
+fn ago(then: u64, now: u64) -> String {
+    let secs: u64 = now.saturating_sub(then);
+    let (count, unit) = match secs {
+        0..=59 => return String::from("just now"),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86399 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
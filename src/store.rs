@@ -0,0 +1,495 @@
+use bincode::{DefaultOptions, Options};
+use dirs::data_local_dir;
+use ron::de::from_str;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use ron::Value;
+use serde::de::IgnoredAny;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{create_dir_all, read, read_dir, remove_file, write};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::game_state::GameState;
+use crate::save::{file_name, SaveError, SlotInfo, QUICKSAVE, SAVE_DIR_VAR, SAVE_VERSION};
+
+/// Somewhere games can be saved to, loaded from, listed, and deleted, by the name of the game and the slot.
+///
+/// A GameState keeps its saves in a [FileStore](struct.FileStore.html) of RON files in the local data directory unless it's given
+/// another with [GameState::set_store()](../game_state/struct.GameState.html#method.set_store). Implement this trait to keep saves
+/// anywhere else, such as a database, using a [Format](enum.Format.html) to turn them into bytes and back.
+///
+/// ```
+/// # use intfic::game_state::GameState;
+/// # use intfic::save::{SaveError, SlotInfo};
+/// # use intfic::store::{Format, SaveStore};
+/// # use std::collections::HashMap;
+/// #[derive(Debug, Default)]
+/// struct Saves(HashMap<String, Vec<u8>>);
+///
+/// impl SaveStore for Saves {
+///     fn write(&mut self, game: &str, info: &SlotInfo, state: &GameState) -> Result<(), SaveError> {
+///         self.0.insert(format!("{}/{}", game, info.slot), Format::Json.encode(info, state)?);
+///         Ok(())
+///     }
+///
+///     fn read(&self, game: &str, slot: &str) -> Result<(SlotInfo, GameState), SaveError> {
+///         match self.0.get(&format!("{}/{}", game, slot)) {
+///             Some(bytes) => Format::Json.decode(slot, bytes),
+///             None => Err(SaveError::Missing { slot: String::from(slot) }),
+///         }
+///     }
+///
+///     fn list(&self, game: &str) -> Result<Vec<SlotInfo>, SaveError> {
+///         let prefix: String = format!("{}/", game);
+///         Ok(self.0.iter().filter(|(key, _)| key.starts_with(&prefix)).filter_map(|(_, bytes)| Format::Json.describe(bytes)).collect())
+///     }
+///
+///     fn delete(&mut self, game: &str, slot: &str) -> Result<(), SaveError> {
+///         self.0.remove(&format!("{}/{}", game, slot)).map(|_| ()).ok_or(SaveError::Missing { slot: String::from(slot) })
+///     }
+/// }
+///
+/// let mut game: GameState = GameState::new("Test GameState");
+/// game.set_store(Saves::default());
+/// game.save_slot("1").unwrap();
+/// assert_eq!(game.slots().unwrap()[0].slot, "1");
+/// ```
+pub trait SaveStore: fmt::Debug + Send + Sync {
+    /// Saves the given GameState of the given game in the slot its SlotInfo names, replacing any save already there.
+    fn write(&mut self, game: &str, info: &SlotInfo, state: &GameState) -> Result<(), SaveError>;
+
+    /// Loads the save in the given slot of the given game, returning [SaveError::Missing](../save/enum.SaveError.html#variant.Missing)
+    /// if there isn't one.
+    fn read(&self, game: &str, slot: &str) -> Result<(SlotInfo, GameState), SaveError>;
+
+    /// Describes every readable save of the given game, in any order.
+    fn list(&self, game: &str) -> Result<Vec<SlotInfo>, SaveError>;
+
+    /// Deletes the save in the given slot of the given game, returning [SaveError::Missing](../save/enum.SaveError.html#variant.Missing)
+    /// if there isn't one.
+    fn delete(&mut self, game: &str, slot: &str) -> Result<(), SaveError>;
+}
+
+/// How a save is turned into bytes to be stored, and back.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Pretty printed RON, easy to read and edit by hand.
+    #[default]
+    Ron,
+    /// Pretty printed JSON, for saves that other programs need to read.
+    Json,
+    /// A compact binary encoding, for saves that should be small and quick to read and write.
+    Binary,
+}
+
+impl Format {
+    /// Returns the file extension used for saves in this Format.
+    ///
+    /// ```
+    /// # use intfic::store::Format;
+    /// assert_eq!(Format::Ron.extension(), "ron");
+    /// assert_eq!(Format::Json.extension(), "json");
+    /// assert_eq!(Format::Binary.extension(), "sav");
+    /// ```
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Ron => "ron",
+            Format::Json => "json",
+            Format::Binary => "sav",
+        }
+    }
+
+    /// Turns a save into bytes, the SlotInfo describing it followed by the GameState.
+    ///
+    /// Binary saves start with the [SAVE_VERSION](../save/constant.SAVE_VERSION.html) they were written in,
+    /// since a SlotInfo in another version of the save format may not be readable at all.
+    ///
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::SlotInfo;
+    /// # use intfic::store::Format;
+    /// let game: GameState = GameState::new("Test GameState");
+    /// let info: SlotInfo = SlotInfo::new("1", &game);
+    ///
+    /// let json: Vec<u8> = Format::Json.encode(&info, &game).unwrap();
+    /// assert!(String::from_utf8(json).unwrap().contains("\"name\": \"Test GameState\""));
+    /// assert!(Format::Binary.encode(&info, &game).unwrap().len() < Format::Ron.encode(&info, &game).unwrap().len());
+    /// ```
+    pub fn encode(&self, info: &SlotInfo, state: &GameState) -> Result<Vec<u8>, SaveError> {
+        let encoded: Result<Vec<u8>, String> = match self {
+            Format::Ron => to_string_pretty(&(info, state), PrettyConfig::new()).map(String::into_bytes).map_err(|e| e.to_string()),
+            Format::Json => serde_json::to_vec_pretty(&(info, state)).map_err(|e| e.to_string()),
+            Format::Binary => DefaultOptions::new().serialize(&(SAVE_VERSION, info, state)).map_err(|e| e.to_string()),
+        };
+        encoded.map_err(|message| SaveError::Serialize { message })
+    }
+
+    /// Turns bytes back into the save in the given slot, upgrading saves written in older versions of the save format.
+    ///
+    /// Returns [SaveError::Version](../save/enum.SaveError.html#variant.Version) for saves written in newer versions,
+    /// and [SaveError::Deserialize](../save/enum.SaveError.html#variant.Deserialize) for bytes that aren't a save in this Format.
+    /// RON saves from before slots had descriptions, which are just the GameState, are read as version 0.
+    ///
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::{SaveError, SlotInfo};
+    /// # use intfic::store::Format;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_counter("gold", 5);
+    /// let bytes: Vec<u8> = Format::Binary.encode(&SlotInfo::new("1", &game), &game).unwrap();
+    ///
+    /// let (info, loaded) = Format::Binary.decode("1", &bytes).unwrap();
+    /// assert_eq!(info.slot, "1");
+    /// assert_eq!(loaded.get_counter("gold"), 5);
+    /// assert!(matches!(Format::Json.decode("1", &bytes), Err(SaveError::Deserialize { .. })));
+    /// ```
+    pub fn decode(&self, slot: &str, bytes: &[u8]) -> Result<(SlotInfo, GameState), SaveError> {
+        let invalid = |e: &dyn fmt::Display| SaveError::Deserialize {
+            slot: String::from(slot),
+            message: e.to_string(),
+        };
+
+        // The version is checked before the game itself is read, since a save in another version may not be readable at all
+        match self {
+            Format::Ron => {
+                let text: &str = str::from_utf8(bytes).map_err(|e| invalid(&e))?;
+                let (info, game): (SlotInfo, Value) = match from_str(text) {
+                    Ok(save) => save,
+                    Err(e) => match from_str::<Value>(text) {
+                        Ok(game @ Value::Map(_)) => (SlotInfo { slot: String::from(slot), ..SlotInfo::default() }, game),
+                        _ => return Err(invalid(&e)),
+                    },
+                };
+                check_version(&info.slot, info.version)?;
+                Ok((info, game.into_rust().map_err(|e| invalid(&e))?))
+            }
+            Format::Json => {
+                let (info, game): (SlotInfo, serde_json::Value) = serde_json::from_slice(bytes).map_err(|e| invalid(&e))?;
+                check_version(&info.slot, info.version)?;
+                Ok((info, serde_json::from_value(game).map_err(|e| invalid(&e))?))
+            }
+            Format::Binary => {
+                let version: u32 = binary(bytes).deserialize(bytes).map_err(|e| invalid(&e))?;
+                check_version(slot, version)?;
+                let (_, info, game): (u32, SlotInfo, GameState) = binary(bytes).deserialize(bytes).map_err(|e| invalid(&e))?;
+                check_version(&info.slot, info.version)?;
+                Ok((info, game))
+            }
+        }
+    }
+
+    /// Reads just the SlotInfo describing a save, or None if the bytes aren't a save in this Format.
+    ///
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::save::SlotInfo;
+    /// # use intfic::store::Format;
+    /// let game: GameState = GameState::new("Test GameState");
+    /// let bytes: Vec<u8> = Format::Ron.encode(&SlotInfo::new("1", &game), &game).unwrap();
+    ///
+    /// assert_eq!(Format::Ron.describe(&bytes).unwrap().slot, "1");
+    /// assert_eq!(Format::Ron.describe(b"not a save"), None);
+    /// ```
+    pub fn describe(&self, bytes: &[u8]) -> Option<SlotInfo> {
+        match self {
+            Format::Ron => from_str::<(SlotInfo, IgnoredAny)>(str::from_utf8(bytes).ok()?).ok().map(|(info, _)| info),
+            Format::Json => serde_json::from_slice::<(SlotInfo, IgnoredAny)>(bytes).ok().map(|(info, _)| info),
+            Format::Binary => binary(bytes).deserialize::<(u32, SlotInfo)>(bytes).ok().map(|(_, info)| info),
+        }
+    }
+}
+
+/// Keeps saves as files in a directory for each game, named after their slots, "\<root>/\<game name>/\<slot>.\<extension>".
+///
+/// Unless the FileStore is given a root, it's the INTFIC_SAVE_DIR environment variable if that's set,
+/// or else "\<local data dir>/rust_intfic".
+///
+/// * On Windows, \<local data dir> corresponds to "C:\Users\\<username>\AppData\Local".
+/// * On macOS, \<local data dir> corresponds to "/Users/\<username>/Library/Application Support".
+/// * On Linux, \<local data dir> corresponds to "/home/\<username>/.local/share".
+///
+/// Game and slot names are made safe to use as file names first, see [save::file_name()](../save/fn.file_name.html).
+/// Saving to a slot whose file already holds a slot with a different name, such as "a:b" and "a/b",
+/// returns [SaveError::Conflict](../save/enum.SaveError.html#variant.Conflict) rather than replacing it.
+///
+/// A RON FileStore also finds the quicksave of a game saved before games had save slots, kept in "\<root>/\<game name>.ron",
+/// until the game is saved to its quicksave slot again.
+///
+/// ```no_run
+/// # use intfic::game_state::GameState;
+/// # use intfic::store::{FileStore, Format};
+/// let mut game: GameState = GameState::new("Test GameState");
+///
+/// game.set_store(FileStore::in_dir("saves", Format::Json));
+/// game.save_slot("1").unwrap(); // writes "saves/Test GameState/1.json"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileStore {
+    /// The directory each game's saves are kept in a directory of, or None for the default.
+    pub root: Option<PathBuf>,
+    /// How saves are written to their files.
+    pub format: Format,
+}
+
+impl FileStore {
+    /// Creates a FileStore that keeps saves in the given Format under the default directory.
+    ///
+    /// ```
+    /// # use intfic::store::{FileStore, Format};
+    /// let store: FileStore = FileStore::new(Format::Binary);
+    ///
+    /// assert_eq!(store.root, None);
+    /// ```
+    pub fn new(format: Format) -> FileStore {
+        FileStore { root: None, format }
+    }
+
+    /// Creates a FileStore that keeps saves in the given Format under the given directory, such as beside a portable install.
+    ///
+    /// ```
+    /// # use intfic::store::{FileStore, Format};
+    /// # use std::path::PathBuf;
+    /// let store: FileStore = FileStore::in_dir("saves", Format::Ron);
+    ///
+    /// assert_eq!(store.dir("Act 1: The Road").unwrap(), PathBuf::from("saves").join("Act 1_ The Road"));
+    /// ```
+    pub fn in_dir<P: Into<PathBuf>>(root: P, format: Format) -> FileStore {
+        FileStore {
+            root: Some(root.into()),
+            format,
+        }
+    }
+
+    /// Returns the directory the given game's saves are kept in, or [SaveError::NoDataDir](../save/enum.SaveError.html#variant.NoDataDir)
+    /// if this FileStore has no root and the local data directory can't be found.
+    ///
+    /// ```no_run
+    /// # use intfic::store::FileStore;
+    /// let store: FileStore = FileStore::default();
+    ///
+    /// println!("{}", store.dir("Act 1: The Road").unwrap().display()); // "/home/<username>/.local/share/rust_intfic/Act 1_ The Road" on Linux
+    /// ```
+    pub fn dir(&self, game: &str) -> Result<PathBuf, SaveError> {
+        Ok(self.root_dir()?.join(file_name(game)))
+    }
+
+    // The directory each game's directory of saves is kept in.
+    fn root_dir(&self) -> Result<PathBuf, SaveError> {
+        match &self.root {
+            Some(root) => Ok(root.clone()),
+            None => default_root(env::var_os(SAVE_DIR_VAR)),
+        }
+    }
+
+    // The file the given slot of the given game is kept in.
+    fn path(&self, game: &str, slot: &str) -> Result<PathBuf, SaveError> {
+        Ok(self.dir(game)?.join(format!("{}.{}", file_name(slot), self.format.extension())))
+    }
+
+    // The name of the slot whose save is in the given file, or None if there isn't a readable one.
+    fn slot_in(&self, path: &Path) -> Option<String> {
+        read(path).ok().and_then(|bytes| self.format.describe(&bytes)).map(|info| info.slot)
+    }
+
+    // The file the save in the given slot of the given game is in, or None if there isn't one.
+    //
+    // Before games had save slots, each game had a single RON save in the root, "<root>/<game name>.ron", which is read as its quicksave.
+    // Those versions wrote the default root as "rust_intfic\", which outside Windows is a directory with a backslash in its name.
+    fn existing(&self, game: &str, slot: &str) -> Result<Option<PathBuf>, SaveError> {
+        let path: PathBuf = self.path(game, slot)?;
+        if path.exists() {
+            return Ok(Some(path));
+        } else if slot != QUICKSAVE || self.format != Format::Ron {
+            return Ok(None);
+        }
+
+        // Those saves were named after the game as it was, so that name is looked for before the safe one
+        let root: PathBuf = self.root_dir()?;
+        let mut roots: Vec<PathBuf> = vec![root.clone()];
+        if let Some(data_dir) = data_local_dir().filter(|data_dir| root == data_dir.join("rust_intfic")) {
+            roots.push(data_dir.join("rust_intfic\\"));
+        }
+        let names: [String; 2] = [format!("{}.ron", game), format!("{}.ron", file_name(game))];
+
+        Ok(roots
+            .iter()
+            .flat_map(|root| names.iter().map(move |name| root.join(name)))
+            .find(|path| path.is_file()))
+    }
+}
+
+impl SaveStore for FileStore {
+    fn write(&mut self, game: &str, info: &SlotInfo, state: &GameState) -> Result<(), SaveError> {
+        let path: PathBuf = self.path(game, &info.slot)?;
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+        }
+
+        if let Some(existing) = self.slot_in(&path).filter(|existing| *existing != info.slot) {
+            return Err(SaveError::Conflict {
+                slot: info.slot.clone(),
+                existing,
+            });
+        }
+
+        let bytes: Vec<u8> = self.format.encode(info, state)?;
+        write(&path, bytes).map_err(|e| io_error(&path, e))
+    }
+
+    fn read(&self, game: &str, slot: &str) -> Result<(SlotInfo, GameState), SaveError> {
+        let path: PathBuf = match self.existing(game, slot)? {
+            Some(path) => path,
+            None => return Err(SaveError::Missing { slot: String::from(slot) }),
+        };
+
+        let bytes: Vec<u8> = read(&path).map_err(|e| io_error(&path, e))?;
+        match self.format.decode(slot, &bytes)? {
+            (info, _) if info.slot != slot => Err(SaveError::Missing { slot: String::from(slot) }),
+            save => Ok(save),
+        }
+    }
+
+    fn list(&self, game: &str) -> Result<Vec<SlotInfo>, SaveError> {
+        let dir: PathBuf = self.dir(game)?;
+
+        // Files that can't be read as saves are left out, so one damaged save doesn't hide the rest
+        let mut slots: Vec<SlotInfo> = if dir.exists() {
+            read_dir(&dir)
+                .map_err(|e| io_error(&dir, e))?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|extension| extension == self.format.extension()))
+                .filter_map(|path| read(path).ok())
+                .filter_map(|bytes| self.format.describe(&bytes))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        if !slots.iter().any(|info| info.slot == QUICKSAVE) {
+            if let Ok((info, _)) = self.read(game, QUICKSAVE) {
+                slots.push(info);
+            }
+        }
+        Ok(slots)
+    }
+
+    fn delete(&mut self, game: &str, slot: &str) -> Result<(), SaveError> {
+        let path: PathBuf = match self.existing(game, slot)? {
+            Some(path) if self.slot_in(&path).is_none_or(|existing| existing == slot) => path,
+            _ => return Err(SaveError::Missing { slot: String::from(slot) }),
+        };
+
+        remove_file(&path).map_err(|e| io_error(&path, e))
+    }
+}
+
+// Encoded saves, by the name of their game and slot.
+type Saves = HashMap<(String, String), Vec<u8>>;
+
+/// Keeps saves in memory, so they never touch the disk and are gone once the program ends.
+///
+/// Clones of a MemoryStore share their saves, so a clone kept aside can see what a GameState has saved.
+///
+/// ```
+/// # use intfic::game_state::GameState;
+/// # use intfic::store::{MemoryStore, SaveStore};
+/// let store: MemoryStore = MemoryStore::new();
+/// let mut game: GameState = GameState::new("Test GameState");
+/// game.set_store(store.clone());
+///
+/// game.save_slot("1").unwrap();
+/// assert_eq!(store.list("Test GameState").unwrap()[0].slot, "1");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    saves: Arc<Mutex<Saves>>,
+}
+
+impl MemoryStore {
+    /// Creates a MemoryStore with no saves in it.
+    ///
+    /// ```
+    /// # use intfic::store::{MemoryStore, SaveStore};
+    /// assert!(MemoryStore::new().list("Test GameState").unwrap().is_empty());
+    /// ```
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    // The saves in this store, still usable if another thread panicked while holding them.
+    fn saves(&self) -> MutexGuard<'_, Saves> {
+        self.saves.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl SaveStore for MemoryStore {
+    fn write(&mut self, game: &str, info: &SlotInfo, state: &GameState) -> Result<(), SaveError> {
+        let bytes: Vec<u8> = Format::Binary.encode(info, state)?;
+        self.saves().insert((String::from(game), info.slot.clone()), bytes);
+        Ok(())
+    }
+
+    fn read(&self, game: &str, slot: &str) -> Result<(SlotInfo, GameState), SaveError> {
+        match self.saves().get(&(String::from(game), String::from(slot))) {
+            Some(bytes) => Format::Binary.decode(slot, bytes),
+            None => Err(SaveError::Missing { slot: String::from(slot) }),
+        }
+    }
+
+    fn list(&self, game: &str) -> Result<Vec<SlotInfo>, SaveError> {
+        Ok(self
+            .saves()
+            .iter()
+            .filter(|((saved_game, _), _)| saved_game == game)
+            .filter_map(|(_, bytes)| Format::Binary.describe(bytes))
+            .collect())
+    }
+
+    fn delete(&mut self, game: &str, slot: &str) -> Result<(), SaveError> {
+        match self.saves().remove(&(String::from(game), String::from(slot))) {
+            Some(_) => Ok(()),
+            None => Err(SaveError::Missing { slot: String::from(slot) }),
+        }
+    }
+}
+
+// The options binary saves are read with. Reading can't claim more memory than the save's own size,
+// and bytes after what's being read are allowed, so a SlotInfo can be read without the GameState after it.
+fn binary(bytes: &[u8]) -> impl Options {
+    DefaultOptions::new().with_limit(bytes.len() as u64).allow_trailing_bytes()
+}
+
+// The directory saves are kept in when a FileStore has no root, given the value of SAVE_DIR_VAR:
+// that directory if it's set and not empty, or else "rust_intfic" in the local data directory.
+pub(crate) fn default_root(save_dir: Option<OsString>) -> Result<PathBuf, SaveError> {
+    match save_dir {
+        Some(root) if !root.is_empty() => Ok(PathBuf::from(root)),
+        _ => Ok(data_local_dir().ok_or(SaveError::NoDataDir)?.join("rust_intfic")),
+    }
+}
+
+// Returns a Version error if the save in the given slot was written in a newer version of the save format than this one.
+fn check_version(slot: &str, version: u32) -> Result<(), SaveError> {
+    if version > SAVE_VERSION {
+        return Err(SaveError::Version {
+            slot: String::from(slot),
+            found: version,
+            expected: SAVE_VERSION,
+        });
+    }
+    Ok(())
+}
+
+// Describes a problem accessing a save file or directory.
+fn io_error(path: &Path, e: io::Error) -> SaveError {
+    SaveError::Io {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    }
+}
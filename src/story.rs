@@ -0,0 +1,249 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::parse_file::{Loader, ParseError};
+use crate::story_block::StoryBlock;
+
+/// Every story file of a story, parsed once and indexed so blocks can be found by file and name without touching the disk again.
+///
+/// Blocks keep the order they were written in within each file, so the first block of a file is the one a story file
+/// option leads to. If a file has two blocks with the same name, the first is the one found by [get](#method.get).
+///
+/// ```
+/// # use intfic::parse_file::Loader;
+/// # use intfic::story::Story;
+/// let loader: Loader = Loader::from_files(vec![
+///     ("start.txt", ":- start\nHello!\n-> next.txt"),
+///     ("next.txt", ":- next\nGoodbye!"),
+/// ]);
+/// let story: Story = Story::load(&loader, &["start.txt"]).unwrap();
+///
+/// assert!(story.contains("next.txt"));
+/// assert_eq!(story.get("next.txt", "next").unwrap().text(), vec!["Goodbye!"]);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Story {
+    files: HashMap<String, Vec<StoryBlock>>,
+    index: HashMap<(String, String), usize>,
+}
+
+impl Story {
+    /// Parses each of the given story files, and every story file reachable from them, into a new Story.
+    ///
+    /// Returns a ParseError if any of the given files can't be loaded.
+    /// See [add](#method.add) for how the files they lead to are treated.
+    ///
+    /// ```no_run
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::story::Story;
+    /// let story: Story = Story::load(&Loader::new("resources"), &["example_1.txt"]).unwrap();
+    ///
+    /// assert!(story.contains("example_2.txt"));
+    /// ```
+    pub fn load<S: AsRef<str>>(loader: &Loader, filenames: &[S]) -> Result<Story, ParseError> {
+        let mut story: Story = Story::default();
+
+        for filename in filenames.iter() {
+            story.add(loader, filename.as_ref())?;
+        }
+        Ok(story)
+    }
+
+    /// Parses the given story file into this Story, along with every story file its options lead to, directly or not.
+    ///
+    /// Returns a ParseError if the given file can't be loaded. Files it leads to that can't be loaded are left out,
+    /// so the problem is only reported if the player actually reaches them.
+    /// Files already in the Story aren't parsed again.
+    ///
+    /// ```
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::story::Story;
+    /// let loader: Loader = Loader::from_files(vec![("start.txt", ":- start\nHello!\n-> missing.txt")]);
+    /// let mut story: Story = Story::default();
+    ///
+    /// assert!(story.add(&loader, "start.txt").is_ok());
+    /// assert!(!story.contains("missing.txt"));
+    /// assert!(story.add(&loader, "missing.txt").is_err());
+    /// ```
+    pub fn add(&mut self, loader: &Loader, filename: &str) -> Result<(), ParseError> {
+        if self.contains(filename) {
+            return Ok(());
+        }
+        self.insert(filename, loader.load(filename)?);
+
+        let mut queue: VecDeque<String> = VecDeque::new();
+        let mut failed: HashSet<String> = HashSet::new();
+        queue.push_back(String::from(filename));
+
+        while let Some(file) = queue.pop_front() {
+            for next in self.linked_files(&file) {
+                if failed.contains(&next) {
+                    continue;
+                }
+
+                match loader.load(&next) {
+                    Ok(blocks) => {
+                        self.insert(&next, blocks);
+                        queue.push_back(next);
+                    }
+                    Err(_) => {
+                        failed.insert(next);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds already parsed blocks to this Story as the contents of the given story file, replacing any it had before.
+    ///
+    /// ```
+    /// # use intfic::story::Story;
+    /// # use intfic::story_block::StoryBlock;
+    /// let mut story: Story = Story::default();
+    /// story.insert("start.txt", vec![StoryBlock::new(String::from("start"))]);
+    ///
+    /// assert_eq!(story.get("start.txt", "start"), Some(&StoryBlock::new(String::from("start"))));
+    /// ```
+    pub fn insert(&mut self, filename: &str, blocks: Vec<StoryBlock>) {
+        self.index.retain(|(file, _), _| file != filename);
+
+        for (i, block) in blocks.iter().enumerate().rev() {
+            self.index.insert((String::from(filename), block.name.clone()), i);
+        }
+        self.files.insert(String::from(filename), blocks);
+    }
+
+    /// Returns true if the given story file has been parsed into this Story.
+    ///
+    /// ```
+    /// # use intfic::story::Story;
+    /// assert_eq!(Story::default().contains("example_1.txt"), false);
+    /// ```
+    pub fn contains(&self, filename: &str) -> bool {
+        self.files.contains_key(filename)
+    }
+
+    /// Returns the block with the given name in the given story file, if there is one.
+    ///
+    /// ```no_run
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::story::Story;
+    /// let story: Story = Story::load(&Loader::new("resources"), &["example_1.txt"]).unwrap();
+    ///
+    /// assert!(story.get("example_1.txt", "neat").is_some());
+    /// assert!(story.get("example_2.txt", "neat").is_none());
+    /// ```
+    pub fn get(&self, filename: &str, name: &str) -> Option<&StoryBlock> {
+        let index: usize = *self.index.get(&(String::from(filename), String::from(name)))?;
+        self.files.get(filename)?.get(index)
+    }
+
+    /// Returns the block with the given name in the given story file, or its first block if the name is empty.
+    ///
+    /// ```no_run
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::story::Story;
+    /// let story: Story = Story::load(&Loader::new("resources"), &["example_1.txt"]).unwrap();
+    ///
+    /// assert_eq!(story.find("example_1.txt", "").unwrap().name, String::from("start"));
+    /// assert_eq!(story.find("example_1.txt", "neat").unwrap().name, String::from("neat"));
+    /// ```
+    pub fn find(&self, filename: &str, name: &str) -> Option<&StoryBlock> {
+        if name.is_empty() {
+            self.first(filename)
+        } else {
+            self.get(filename, name)
+        }
+    }
+
+    /// Returns the first block of the given story file, where the story picks up when an option leads to the file.
+    ///
+    /// ```no_run
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::story::Story;
+    /// let story: Story = Story::load(&Loader::new("resources"), &["example_1.txt"]).unwrap();
+    ///
+    /// assert_eq!(story.first("example_2.txt").unwrap().name, String::from("start"));
+    /// ```
+    pub fn first(&self, filename: &str) -> Option<&StoryBlock> {
+        self.files.get(filename)?.first()
+    }
+
+    /// Returns every block in the given story file, in the order they were written.
+    ///
+    /// ```no_run
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::story::Story;
+    /// let story: Story = Story::load(&Loader::new("resources"), &["example_1.txt"]).unwrap();
+    ///
+    /// assert_eq!(story.blocks("example_1.txt").unwrap()[0].name, String::from("start"));
+    /// ```
+    pub fn blocks(&self, filename: &str) -> Option<&[StoryBlock]> {
+        self.files.get(filename).map(Vec::as_slice)
+    }
+
+    /// Returns the names of every story file in this Story, in no particular order.
+    ///
+    /// ```no_run
+    /// # use intfic::parse_file::Loader;
+    /// # use intfic::story::Story;
+    /// let story: Story = Story::load(&Loader::new("resources"), &["example_1.txt"]).unwrap();
+    ///
+    /// assert_eq!(story.files().count(), 2);
+    /// ```
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    // Returns the story files the options of the given file lead to that aren't in the Story yet.
+    fn linked_files(&self, filename: &str) -> Vec<String> {
+        let mut linked: Vec<String> = Vec::new();
+
+        for block in self.files[filename].iter() {
+            for choice in block.choices() {
+                for (file, _) in targets(&choice.result).into_iter().filter_map(split_target) {
+                    if !self.contains(file) && !linked.iter().any(|linked| linked == file) {
+                        linked.push(String::from(file));
+                    }
+                }
+            }
+        }
+        linked
+    }
+}
+
+/// Splits the result of an option into the story file and block it leads to, if it leads to another story file.
+///
+/// A result like "chapter2.txt#ambush" leads to the "ambush" block of "chapter2.txt", while a result like "chapter2.txt"
+/// leads to the first block of "chapter2.txt", so its block name is empty. Any other result is the name of a block in the same file.
+///
+/// ```
+/// # use intfic::story::split_target;
+/// assert_eq!(split_target("chapter2.txt#ambush"), Some(("chapter2.txt", "ambush")));
+/// assert_eq!(split_target("chapter2.txt"), Some(("chapter2.txt", "")));
+/// assert_eq!(split_target("ambush"), None);
+/// ```
+pub fn split_target(result: &str) -> Option<(&str, &str)> {
+    match result.find('#') {
+        Some(split) => Some((&result[..split], &result[split + 1..])),
+        None if result.ends_with(".txt") => Some((result, "")),
+        None => None,
+    }
+}
+
+/// Returns every target the result of an option may lead to.
+///
+/// A result like "?- has_key => vault => locked_door" picks one of two targets depending on its condition, while any other result is a single target.
+///
+/// ```
+/// # use intfic::story::targets;
+/// assert_eq!(targets("?- has_key => vault => chapter2.txt#locked_door"), vec!["vault", "chapter2.txt#locked_door"]);
+/// assert_eq!(targets("vault"), vec!["vault"]);
+/// ```
+pub fn targets(result: &str) -> Vec<&str> {
+    if result.starts_with("?-") || result.starts_with("#-") {
+        result.split(" => ").skip(1).collect()
+    } else {
+        vec![result]
+    }
+}
@@ -1,30 +1,261 @@
-use std::collections::HashMap;
-
+use crate::engine::Event;
+use crate::expr::Expr;
 use crate::game_state::GameState;
-use crate::parse_file::load_file;
-use crate::parse_input::{get_input, query, sanitize};
-use crate::print_debug;
-use crate::write_out::{type_text, Color};
+use crate::parse_input::{query, sanitize};
+use crate::template::interpolate;
+use crate::util::{shuffled, stable_hash};
+use crate::write_out::Color;
 
 /// StoryBlocks are atomic chunks of interactive narrative.
 /// 
-/// They have a name, a list of text that will be presented to the player,
-/// a list of options that will be presented to the player, and a series of effects that will be applied to the GameState (flag or counter alterations).
+/// They have a name and a body of statements that are carried out in order when the block is read.
 /// 
-/// Text and options may be filtered based on conditionals that check flags and counters in the current GameState.
-/// However, such conditions must pass at the **start** of the block. e.g. you cannot set a flag and get the result you just set in the same block.
+/// The body holds the lines of text that will be presented to the player, the effects that will be applied to the GameState
+/// (flag or counter alterations), the questions the player will be asked, and the options they may choose from, in the order they were written.
+/// Conditions are checked against the GameState as it is when they're reached, so a flag set on one line affects a conditional on the next.
+/// Options are offered, and their own conditions checked, once the whole body has been carried out.
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct StoryBlock {
     /// The name of the storyblock, may be referenced as the "result" of options.
     pub name: String,
-    /// The text that will be typed out line by line, your story!
-    pub text: Vec<String>,
-    /// The options available to choose from by the player.
-    pub options: Vec<Choice>,
-    /// The flags that will be applied to our GameState by this block.
-    pub flags: HashMap<String, bool>,
-    /// The counters that will be applied to our GameState by this block.
-    pub counters: HashMap<String, i32>,
+    /// The text, effects, questions, and options of this block, your story! Carried out top to bottom.
+    pub body: Vec<Statement>,
+}
+
+/// A single line of a block's body.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// A line of text that will be typed out, which may start with a color.
+    Text(String),
+    /// `?- condition => then => otherwise` types out one of two lines of text, or only the first, depending on a condition.
+    Conditional(Conditional),
+    /// `=- name = value` sets a flag in our GameState.
+    SetFlag(String, bool),
+    /// `+- name OP value` changes a counter in our GameState.
+    Counter(String, Update),
+    /// `$- name => question => rules` asks the player a question and stores their answer.
+    Prompt(Prompt),
+    /// `*- text -> typed -> result -> effects` or `-> result` offers an option to the player once the body has been carried out.
+    Choice(Choice),
+    /// `if-` ... `endif-` or `switch-` ... `endswitch-` carries out only the statements of the first branch that passes.
+    Section(Section),
+    /// `~- kind => first => second` shows a different line each time it's read.
+    Sequence(Sequence),
+}
+
+/// A conditional section of a block's body, which may hold any statements, including other sections.
+///
+/// An `if-` section has a branch for its `if-` and each `elif-`, and its `else-` statements are carried out if none pass.
+/// A `switch-` section has a branch for each `case-`, comparing the switched value to the case's values, and its `default-` statements are carried out if none match.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Section {
+    /// The condition of each branch, and the statements carried out if it's the first to pass.
+    pub branches: Vec<(Expr, Vec<Statement>)>,
+    /// The statements carried out if no branch passes.
+    pub otherwise: Vec<Statement>,
+}
+
+impl Section {
+    /// Returns the statements of the first branch whose condition passes in our GameState, or the fallback statements if none do.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::{Section, Statement};
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// let section = Section {
+    ///     branches: vec![(Expr::parse("has_key").unwrap(), vec![Statement::Text(String::from("You unlock the door."))])],
+    ///     otherwise: vec![Statement::Text(String::from("The door is locked."))],
+    /// };
+    ///
+    /// assert_eq!(section.branch(&game), &[Statement::Text(String::from("The door is locked."))]);
+    /// game.set_flag("has_key", true);
+    /// assert_eq!(section.branch(&game), &[Statement::Text(String::from("You unlock the door."))]);
+    /// ```
+    pub fn branch(&self, game: &GameState) -> &[Statement] {
+        self.branches
+            .iter()
+            .find(|(condition, _)| condition.is_true(game))
+            .map_or(&self.otherwise, |(_, statements)| statements)
+    }
+}
+
+/// How a sequence picks the line to show each time it's read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SequenceKind {
+    /// `stopping` shows each line in turn, then keeps showing the last one.
+    Stopping,
+    /// `cycle` shows each line in turn, then starts over from the first.
+    Cycle,
+    /// `shuffle` shows every line once in a random order, then shuffles them again.
+    Shuffle,
+    /// `once` shows each line in turn, then nothing at all.
+    Once,
+}
+
+/// Lines of text that take turns being shown each time a block is read, written in story files as `~- kind => first => second`.
+///
+/// How many times each sequence has been read is kept in our GameState, so they pick up where they left off when a game is loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    /// How the line to show is picked.
+    pub kind: SequenceKind,
+    /// The lines to pick from, which may start with a color.
+    pub lines: Vec<String>,
+    /// Which sequence of its block this is, counting from 0, so its reads can be told apart from those of the block's other sequences.
+    pub index: usize,
+}
+
+impl Sequence {
+    /// Returns the line this sequence shows when it has been read the given number of times before, or None if it shows nothing.
+    ///
+    /// A shuffle's order is worked out from the given seed and reference, so it's the same every time the same sequence is read
+    /// in a game with the same [seed](../game_state/struct.GameState.html#structfield.seed), on every platform and version of this crate.
+    ///
+    /// ```
+    /// # use intfic::story_block::{Sequence, SequenceKind};
+    /// let mut sequence = Sequence {
+    ///     kind: SequenceKind::Stopping,
+    ///     lines: vec![String::from("A bell rings."), String::from("The bell rings again.")],
+    ///     index: 0,
+    /// };
+    ///
+    /// assert_eq!(sequence.line(0, "example_1.txt#start~0", 0), Some("A bell rings."));
+    /// assert_eq!(sequence.line(5, "example_1.txt#start~0", 0), Some("The bell rings again."));
+    /// sequence.kind = SequenceKind::Cycle;
+    /// assert_eq!(sequence.line(2, "example_1.txt#start~0", 0), Some("A bell rings."));
+    /// sequence.kind = SequenceKind::Once;
+    /// assert_eq!(sequence.line(2, "example_1.txt#start~0", 0), None);
+    /// ```
+    pub fn line(&self, read: u32, reference: &str, seed: u64) -> Option<&str> {
+        let len: usize = self.lines.len();
+        let read: usize = read as usize;
+
+        let index: usize = match self.kind {
+            _ if len == 0 => return None,
+            SequenceKind::Stopping => read.min(len - 1),
+            SequenceKind::Cycle => read % len,
+            SequenceKind::Once if read >= len => return None,
+            SequenceKind::Once => read,
+            SequenceKind::Shuffle => {
+                let round: u64 = (read / len) as u64;
+                let hash: u64 = stable_hash(seed.to_le_bytes().iter().chain(reference.as_bytes()).chain(&[0]).chain(&round.to_le_bytes()).copied());
+                shuffled(len, hash)[read % len]
+            }
+        };
+        Some(&self.lines[index])
+    }
+
+    // The reference this sequence's reads are kept under in our GameState, based on the block currently presented.
+    fn reference(&self, game: &GameState) -> String {
+        format!("{}#{}~{}", game.progress.0, game.progress.1, self.index)
+    }
+}
+
+/// What should happen once a Statement has been carried out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Next<'a> {
+    /// Carry on with the next statement.
+    Continue,
+    /// Ask the player this question, and carry on once they've answered it.
+    Ask(&'a Prompt),
+    /// Carry out these statements from a section, then carry on after it.
+    Enter(&'a [Statement]),
+    /// Offer this option once the body has been carried out.
+    Offer(&'a Choice),
+}
+
+/// A line of text shown only if its condition passes, with an optional line shown instead if it fails.
+///
+/// Written in story files as `?- condition => then` or `?- condition => then => otherwise`, where `#-` may be used in place of `?-`.
+/// The condition is parsed once when the story file is loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conditional {
+    /// The condition checked against our GameState each time the line is reached.
+    pub condition: Expr,
+    /// The line of text shown if the condition passes, which may start with a color.
+    pub then: String,
+    /// The line of text shown if the condition fails, if any.
+    pub otherwise: Option<String>,
+}
+
+impl Conditional {
+    /// Returns the line of text that should be shown in our GameState, or None if the condition fails and there's no line for that.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::Conditional;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// let conditional = Conditional {
+    ///     condition: Expr::parse("gold >= 10").unwrap(),
+    ///     then: String::from("You can afford the sword."),
+    ///     otherwise: Some(String::from("-r You can't afford the sword.")),
+    /// };
+    ///
+    /// assert_eq!(conditional.pick(&game), Some("-r You can't afford the sword."));
+    /// game.set_counter("gold", 10);
+    /// assert_eq!(conditional.pick(&game), Some("You can afford the sword."));
+    /// ```
+    pub fn pick(&self, game: &GameState) -> Option<&str> {
+        if self.condition.is_true(game) {
+            Some(&self.then)
+        } else {
+            self.otherwise.as_deref()
+        }
+    }
+}
+
+impl Statement {
+    /// Carries out this statement in our GameState, adding any lines it produces to the given events, and returns what should happen next.
+    ///
+    /// A prompt can't be carried out without the player's answer, and options and sections affect what comes after them,
+    /// so those are returned for the caller to deal with instead.
+    ///
+    /// ```
+    /// # use intfic::engine::Event;
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::{Conditional, Statement};
+    /// # use intfic::write_out::Color;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// let mut events: Vec<Event> = Vec::new();
+    ///
+    /// Statement::SetFlag(String::from("door_open"), true).execute(&mut game, &mut events);
+    /// Statement::Conditional(Conditional {
+    ///     condition: Expr::parse("door_open").unwrap(),
+    ///     then: String::from("The door creaks open."),
+    ///     otherwise: None,
+    /// }).execute(&mut game, &mut events);
+    ///
+    /// assert_eq!(events, vec![Event::Line(String::from("The door creaks open."), Color::White)]);
+    /// ```
+    pub fn execute(&self, game: &mut GameState, events: &mut Vec<Event>) -> Next<'_> {
+        match self {
+            Statement::Text(line) => read_line(line, game, events),
+            Statement::Conditional(conditional) => {
+                if let Some(line) = conditional.pick(game) {
+                    read_line(line, game, events);
+                }
+            }
+            Statement::SetFlag(name, value) => game.set_flag(name, *value),
+            Statement::Counter(name, update) => {
+                let new_val: i32 = update.apply(game.get_counter(name), game);
+                game.set_counter(name, new_val);
+            }
+            Statement::Prompt(prompt) => return Next::Ask(prompt),
+            Statement::Choice(choice) => return Next::Offer(choice),
+            Statement::Section(section) => return Next::Enter(section.branch(game)),
+            Statement::Sequence(sequence) => {
+                let reference: String = sequence.reference(game);
+                let read: u32 = game.advance_sequence(&reference);
+                if let Some(line) = sequence.line(read, &reference, game.seed) {
+                    read_line(line, game, events);
+                }
+            }
+        }
+        Next::Continue
+    }
 }
 
 /// A choice has some text that the player will see, a list of words to match input against, and a result.
@@ -35,36 +266,359 @@ pub struct StoryBlock {
 pub struct Choice {
     /// The string that will be typed out and presented to the player for this option.
     pub text: String,
+    /// The condition that must pass for this option to be offered, written before its text like "?- has_key => Open the door".
+    pub condition: Option<Expr>,
     /// If the user types a substring of this string, the option will be selected.
     pub typed: String,
-    /// Corresponds to the name of a story block or story file
+    /// Corresponds to the name of a story block or story file, or picks one of two with a condition like "?- has_key => vault => locked_door"
     pub result: String,
+    /// The condition of a result that picks one of two targets, parsed from the result when the story file is loaded.
+    pub result_condition: Option<Expr>,
+    /// Whether this option is always offered, only until it's chosen, or only when nothing else is.
+    pub kind: ChoiceKind,
+    /// Which option of its block this is, counting from 0, so the times it's chosen can be told apart from those of the block's other options.
+    pub index: usize,
+    /// Flags set and counters updated when this option is chosen, before the story carries on to its result.
+    pub effects: Vec<Statement>,
 }
 
-impl Choice {
-    // Print out the text of a Choice with a number before it to produce an ordered list.
-    fn present(&self, num: i32, _game: &mut GameState) {
-        let numbered_option: &str = &format!("{}) {}", num, &self.text)[..];
-        type_text(numbered_option, Color::White, true);
+/// When an option is offered, written in story files as a marker at the start of the option's text like `*- [once] text -> typed -> result`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ChoiceKind {
+    /// `[sticky]`, or no marker, offers the option every time its block is read.
+    #[default]
+    Sticky,
+    /// `[once]` stops offering the option once it has been chosen.
+    Once,
+    /// `[fallback]` offers the option only if no other option of its block is available.
+    Fallback,
+}
+
+impl ChoiceKind {
+    /// Splits the marker off the start of an option's text, returning the kind of option it marks and the rest of the text.
+    ///
+    /// Text without a marker is a sticky option.
+    ///
+    /// ```
+    /// # use intfic::story_block::ChoiceKind;
+    /// assert_eq!(ChoiceKind::split_marker("[once] Ask about the key"), (ChoiceKind::Once, "Ask about the key"));
+    /// assert_eq!(ChoiceKind::split_marker("[fallback] ?- tired => Leave"), (ChoiceKind::Fallback, "?- tired => Leave"));
+    /// assert_eq!(ChoiceKind::split_marker("[Leave]"), (ChoiceKind::Sticky, "[Leave]"));
+    /// ```
+    pub fn split_marker(text: &str) -> (ChoiceKind, &str) {
+        if let Some(rest) = text.strip_prefix("[once] ") {
+            (ChoiceKind::Once, rest)
+        } else if let Some(rest) = text.strip_prefix("[fallback] ") {
+            (ChoiceKind::Fallback, rest)
+        } else {
+            (ChoiceKind::Sticky, text.strip_prefix("[sticky] ").unwrap_or(text))
+        }
+    }
+}
+
+/// A change to a counter, written in story files as `+- name OP value` where OP is one of `+`, `-`, `*`, or `=`.
+///
+/// The value may be a whole number, another counter, or any [Expr](../expr/enum.Expr.html) that calculates a number.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Update {
+    /// `+- name + value` adds the value to the counter.
+    Add(Expr),
+    /// `+- name - value` subtracts the value from the counter.
+    Subtract(Expr),
+    /// `+- name * value` multiplies the counter by the value.
+    Multiply(Expr),
+    /// `+- name = value` sets the counter to the value, such as copying another counter with `+- gold = stash`.
+    Set(Expr),
+}
+
+impl Update {
+    /// Returns what a counter with the given value becomes after this update, calculating the update's value in the given GameState.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::Update;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.update_counter("damage", 3);
+    ///
+    /// assert_eq!(Update::Subtract(Expr::parse("damage * 2").unwrap()).apply(10, &game), 4);
+    /// assert_eq!(Update::Set(Expr::parse("damage").unwrap()).apply(10, &game), 3);
+    /// ```
+    pub fn apply(&self, current: i32, game: &GameState) -> i32 {
+        match self {
+            Update::Add(value) => current.saturating_add(value.value(game)),
+            Update::Subtract(value) => current.saturating_sub(value.value(game)),
+            Update::Multiply(value) => current.saturating_mul(value.value(game)),
+            Update::Set(value) => value.value(game),
+        }
+    }
+
+    /// Returns the names of every counter this update's value reads, in alphabetical order.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::story_block::Update;
+    /// assert_eq!(Update::Add(Expr::parse("gold + bonus").unwrap()).counters(), vec!["bonus", "gold"]);
+    /// assert_eq!(Update::Set(Expr::parse("stash").unwrap()).counters(), vec!["stash"]);
+    /// ```
+    pub fn counters(&self) -> Vec<&str> {
+        self.value().value_variables().1
+    }
+
+    /// Returns the names of every flag this update's value reads, in alphabetical order.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::story_block::Update;
+    /// assert_eq!(Update::Add(Expr::parse("(has_key == true) * 5").unwrap()).flags(), vec!["has_key"]);
+    /// assert!(Update::Set(Expr::parse("stash").unwrap()).flags().is_empty());
+    /// ```
+    pub fn flags(&self) -> Vec<&str> {
+        self.value().value_variables().0
     }
 
-    // Searches the text, "typed" string, and number corresponding with an option for the given input string
-    //
-    // This determines if the player was selecting that option.
-    fn match_option(&self, input: &str, num: i32) -> bool {
+    // The value this update calculates.
+    fn value(&self) -> &Expr {
+        match self {
+            Update::Add(value) | Update::Subtract(value) | Update::Multiply(value) | Update::Set(value) => value,
+        }
+    }
+}
+
+/// A question the player answers with free text, written in story files as `$- name => question => rules`.
+///
+/// The answer is stored as a string in our GameState under the given name.
+/// Rules are optional and separated by commas: `nonempty` requires an answer, and `max N` limits it to N characters.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Prompt {
+    /// The name of the string the answer is stored in.
+    pub variable: String,
+    /// The question the player is asked.
+    pub question: String,
+    /// If true, an empty answer isn't accepted.
+    pub nonempty: bool,
+    /// The most characters an answer may have, if limited.
+    pub max_len: Option<usize>,
+}
+
+impl Prompt {
+    /// Checks the player's answer against this prompt's rules, returning the trimmed answer or a description of what's wrong with it.
+    ///
+    /// ```
+    /// # use intfic::story_block::Prompt;
+    /// let prompt = Prompt {
+    ///     variable: String::from("player_name"),
+    ///     question: String::from("What's your name?"),
+    ///     nonempty: true,
+    ///     max_len: Some(5),
+    /// };
+    ///
+    /// assert_eq!(prompt.validate("  Sam\n"), Ok(String::from("Sam")));
+    /// assert!(prompt.validate("").is_err());
+    /// assert!(prompt.validate("Samantha").is_err());
+    /// ```
+    pub fn validate(&self, input: &str) -> Result<String, String> {
+        let answer: &str = input.trim();
+
+        match self.max_len {
+            _ if self.nonempty && answer.is_empty() => Err(String::from("Please enter an answer.")),
+            Some(max_len) if answer.chars().count() > max_len => {
+                Err(format!("Please keep your answer to {} characters or fewer.", max_len))
+            }
+            _ => Ok(String::from(answer)),
+        }
+    }
+
+    /// Returns this prompt's question with its placeholders filled in from our GameState, or as written if they can't be.
+    ///
+    /// ```
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::Prompt;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_string("player_name", "Sam");
+    /// let prompt = Prompt {
+    ///     variable: String::from("pet_name"),
+    ///     question: String::from("What's your pet's name, {player_name}?"),
+    ///     ..Prompt::default()
+    /// };
+    ///
+    /// assert_eq!(prompt.filled_question(&game), "What's your pet's name, Sam?");
+    /// ```
+    pub fn filled_question(&self, game: &GameState) -> String {
+        interpolate(&self.question, game).unwrap_or_else(|_| self.question.clone())
+    }
+}
+
+impl Choice {
+    /// Searches the text, "typed" string, and number corresponding with an option for the given input string.
+    ///
+    /// This determines if the player was selecting that option.
+    ///
+    /// ```
+    /// # use intfic::story_block::Choice;
+    /// let choice = Choice {
+    ///     text: String::from("Keep walking"),
+    ///     typed: String::from("walk"),
+    ///     result: String::from("walk_car"),
+    ///     ..Choice::default()
+    /// };
+    ///
+    /// assert!(choice.matches("keep walking", 1));
+    /// assert!(choice.matches("walk", 1));
+    /// assert!(choice.matches("1", 1));
+    /// assert!(!choice.matches("run", 1));
+    /// ```
+    pub fn matches(&self, input: &str, num: usize) -> bool {
         sanitize(self.text.clone()) == *input
             || self.result == *input
             || num.to_string() == *input
             || self.typed.contains(input)
             || self.typed.starts_with('@') && query(&(self.typed[..]), input)
     }
+
+    /// Returns this option as the player should see it if its condition passes in our GameState, or None if it has a condition that fails.
+    ///
+    /// Any placeholders in the text are filled in. One that can't be filled in is shown as written.
+    /// A `[once]` option that has already been chosen from the block currently presented isn't available either.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::{Choice, ChoiceKind};
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// let choice = Choice {
+    ///     text: String::from("Open the door"),
+    ///     condition: Some(Expr::parse("has_key").unwrap()),
+    ///     typed: String::from("open"),
+    ///     result: String::from("open_door"),
+    ///     kind: ChoiceKind::Once,
+    ///     ..Choice::default()
+    /// };
+    ///
+    /// assert_eq!(choice.available(&game), None);
+    /// game.set_flag("has_key", true);
+    /// assert_eq!(choice.available(&game).unwrap().text, String::from("Open the door"));
+    /// choice.choose(&mut game);
+    /// assert_eq!(choice.available(&game), None);
+    /// ```
+    pub fn available(&self, game: &GameState) -> Option<Choice> {
+        if self.kind == ChoiceKind::Once && game.times_chosen(&self.reference(game)) > 0 {
+            return None;
+        }
+
+        if self.condition.as_ref().is_some_and(|condition| !condition.is_true(game)) {
+            return None;
+        }
+
+        Some(Choice {
+            text: interpolate(&self.text, game).unwrap_or_else(|_| self.text.clone()),
+            ..self.clone()
+        })
+    }
+
+    /// Returns the target this option leads to, picking between the two targets of a result like "?- has_key => vault => locked_door"
+    /// by checking its condition in our GameState. A result without a condition is the target itself.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::Choice;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// let choice = Choice {
+    ///     text: String::from("Go inside"),
+    ///     result: String::from("?- has_key => vault => locked_door"),
+    ///     result_condition: Some(Expr::parse("has_key").unwrap()),
+    ///     ..Choice::default()
+    /// };
+    ///
+    /// assert_eq!(choice.target(&game), "locked_door");
+    /// game.set_flag("has_key", true);
+    /// assert_eq!(choice.target(&game), "vault");
+    /// ```
+    pub fn target(&self, game: &GameState) -> &str {
+        let condition: &Expr = match &self.result_condition {
+            Some(condition) => condition,
+            None => return &self.result,
+        };
+
+        let mut target_split = self.result.split(" => ").skip(1);
+        if condition.is_true(game) {
+            target_split.next().unwrap_or_default()
+        } else {
+            target_split.nth(1).unwrap_or_default()
+        }
+    }
+
+    /// Records in our GameState that the player chose this option from the block currently presented, and applies its effects.
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::{Choice, Statement, Update};
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// game.set_progress("example_1.txt", "start");
+    /// game.update_counter("gold", 15);
+    /// let choice = Choice {
+    ///     text: String::from("Buy a sword"),
+    ///     effects: vec![
+    ///         Statement::Counter(String::from("gold"), Update::Subtract(Expr::Int(10))),
+    ///         Statement::SetFlag(String::from("has_sword"), true),
+    ///     ],
+    ///     ..Choice::default()
+    /// };
+    ///
+    /// choice.choose(&mut game);
+    /// assert_eq!(game.times_chosen("example_1.txt#start*0"), 1);
+    /// assert_eq!(game.get_counter("gold"), 5);
+    /// assert_eq!(game.get_flag("has_sword"), true);
+    /// ```
+    pub fn choose(&self, game: &mut GameState) {
+        let reference: String = self.reference(game);
+        game.choose(&reference);
+
+        // Effects only set flags and update counters, so they never produce any lines
+        for effect in self.effects.iter() {
+            effect.execute(game, &mut Vec::new());
+        }
+    }
+
+    // The reference the times this option is chosen are kept under in our GameState, based on the block currently presented.
+    fn reference(&self, game: &GameState) -> String {
+        format!("{}#{}*{}", game.progress.0, game.progress.1, self.index)
+    }
+}
+
+/// Returns the given options as the player should see them, leaving out those that aren't [available](struct.Choice.html#method.available).
+///
+/// `[fallback]` options are only included if no other option is available.
+///
+/// ```
+/// # use intfic::expr::Expr;
+/// # use intfic::game_state::GameState;
+/// # use intfic::story_block::{available_choices, Choice, ChoiceKind};
+/// let game: GameState = GameState::new("Test GameState");
+/// let ask = Choice { text: String::from("Ask about the key"), condition: Some(Expr::parse("curious").unwrap()), ..Choice::default() };
+/// let leave = Choice { text: String::from("Leave"), kind: ChoiceKind::Fallback, ..Choice::default() };
+///
+/// assert_eq!(available_choices(vec![&ask, &leave], &game)[0].text, String::from("Leave"));
+/// ```
+pub fn available_choices<'a>(choices: impl IntoIterator<Item = &'a Choice>, game: &GameState) -> Vec<Choice> {
+    let (fallbacks, options): (Vec<Choice>, Vec<Choice>) = choices
+        .into_iter()
+        .filter_map(|choice| choice.available(game))
+        .partition(|choice| choice.kind == ChoiceKind::Fallback);
+
+    if options.is_empty() {
+        fallbacks
+    } else {
+        options
+    }
 }
 
 impl StoryBlock {
     /// Returns an empty story block with the given name.
     /// 
     /// ```
-    /// # use std::collections::HashMap;
     /// # use intfic::story_block::StoryBlock;
     /// let mut block: StoryBlock = StoryBlock::new(String::from("Test GameState"));
     /// 
@@ -72,253 +626,270 @@ impl StoryBlock {
     ///     block, 
     ///     StoryBlock {
     ///         name: String::from("Test GameState"),
-    ///         text: Vec::new(),
-    ///         options: Vec::new(),
-    ///         flags: HashMap::new(),
-    ///         counters: HashMap::new(),
+    ///         body: Vec::new(),
     ///     }
     /// );
     /// ```
     pub fn new(name_in: String) -> StoryBlock {
         StoryBlock {
             name: name_in,
-            text: Vec::new(),
-            options: Vec::new(),
-            flags: HashMap::new(),
-            counters: HashMap::new(),
+            body: Vec::new(),
         }
     }
 
-    // Plays out the contents and effects of a block, then presents the options to the player.
-    //
-    // Also updates the GameState progress with this block's name.
-    fn read(&self, game: &mut GameState, blocks: &[StoryBlock]) {
+    /// Carries out the body of this block top to bottom, adding the lines it produces to the given events.
+    ///
+    /// Also updates the GameState progress with this block's name, and counts the visit.
+    /// Only the branch that passes is carried out for each section.
+    /// Questions are skipped, since they need the player to answer them. The [Engine](../engine/struct.Engine.html) asks them as it reaches them.
+    ///
+    /// ```
+    /// # use intfic::engine::Event;
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::{Conditional, Statement, StoryBlock};
+    /// # use intfic::write_out::Color;
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// let mut block: StoryBlock = StoryBlock::new(String::from("test_block"));
+    /// block.body.push(Statement::Text(String::from("-y Hello!")));
+    /// block.body.push(Statement::SetFlag(String::from("greeted"), true));
+    /// block.body.push(Statement::Conditional(Conditional {
+    ///     condition: Expr::parse("greeted").unwrap(),
+    ///     then: String::from("Nice to meet you."),
+    ///     otherwise: None,
+    /// }));
+    ///
+    /// let mut events: Vec<Event> = Vec::new();
+    /// block.read(&mut game, &mut events);
+    ///
+    /// assert_eq!(events, vec![
+    ///     Event::Line(String::from("Hello!"), Color::Yellow),
+    ///     Event::Line(String::from("Nice to meet you."), Color::White),
+    ///     Event::Break,
+    /// ]);
+    /// assert_eq!(game.progress.1, String::from("test_block"));
+    /// assert_eq!(game.get_flag("greeted"), true);
+    /// ```
+    pub fn read(&self, game: &mut GameState, events: &mut Vec<Event>) {
         game.progress.1 = self.name.clone();
-        self.read_text(game);
-        self.apply_effects(game);
-        self.present_options(game, blocks);
+        let story: String = game.progress.0.clone();
+        game.visit(&story, &self.name);
+        carry_out(&self.body, game, events);
+        events.push(Event::Break);
     }
 
-    // Reads the text of this block line by line.
-    fn read_text(&self, game: &GameState) {
-        for line in self.text.iter() {
-            read_line(line, game);
-        }
+    /// Reads the text of this block line by line, without applying any of its effects.
+    ///
+    /// Sections show the text of the branch that passes in our GameState as it is now, and sequences show the line they showed last.
+    ///
+    /// ```
+    /// # use intfic::engine::Event;
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::{Conditional, Statement, StoryBlock};
+    /// # use intfic::write_out::Color;
+    /// let game: GameState = GameState::new("Test GameState");
+    /// let mut block: StoryBlock = StoryBlock::new(String::from("test_block"));
+    /// block.body.push(Statement::SetFlag(String::from("not_set_flag"), true));
+    /// block.body.push(Statement::Conditional(Conditional {
+    ///     condition: Expr::parse("not_set_flag").unwrap(),
+    ///     then: String::from("hidden"),
+    ///     otherwise: Some(String::from("shown")),
+    /// }));
+    ///
+    /// let mut events: Vec<Event> = Vec::new();
+    /// block.read_text(&game, &mut events);
+    ///
+    /// assert_eq!(events, vec![Event::Line(String::from("shown"), Color::White), Event::Break]);
+    /// ```
+    pub fn read_text(&self, game: &GameState, events: &mut Vec<Event>) {
+        read_lines(&self.body, game, events);
+        events.push(Event::Break);
+    }
 
-        println!();
+    /// Returns the lines of text in this block's body in the order they were written, including those in every branch of its sections
+    /// and both lines of its conditional text.
+    ///
+    /// ```
+    /// # use intfic::story_block::{Statement, StoryBlock};
+    /// let mut block: StoryBlock = StoryBlock::new(String::from("test_block"));
+    /// block.body.push(Statement::Text(String::from("Hello!")));
+    /// block.body.push(Statement::SetFlag(String::from("greeted"), true));
+    ///
+    /// assert_eq!(block.text(), vec!["Hello!"]);
+    /// ```
+    pub fn text(&self) -> Vec<&str> {
+        self.statements()
+            .into_iter()
+            .flat_map(|statement| match statement {
+                Statement::Text(line) => vec![line.as_str()],
+                Statement::Conditional(conditional) => {
+                    std::iter::once(conditional.then.as_str()).chain(conditional.otherwise.as_deref()).collect()
+                }
+                _ => Vec::new(),
+            })
+            .collect()
     }
 
-    // Applies any flags or counters associated with this block to the GameState flag and counter environments.
-    fn apply_effects(&self, game: &mut GameState) {
-        for (k, v) in self.flags.iter() {
-            game.set_flag(k, *v);
-        }
+    /// Returns every option in this block's body in the order they were written, including those in every branch of its sections.
+    ///
+    /// ```
+    /// # use intfic::parse_file::parse_str;
+    /// let blocks = parse_str("inline.txt", ":- start\nif- has_key\n*- Open the door -> open -> door\nendif-\n*- Leave -> leave -> end").unwrap();
+    /// let results: Vec<&str> = blocks[0].choices().iter().map(|choice| choice.result.as_str()).collect();
+    ///
+    /// assert_eq!(results, vec!["door", "end"]);
+    /// ```
+    pub fn choices(&self) -> Vec<&Choice> {
+        self.statements()
+            .into_iter()
+            .filter_map(|statement| match statement {
+                Statement::Choice(choice) => Some(choice),
+                _ => None,
+            })
+            .collect()
+    }
 
-        for (k, v) in self.counters.iter() {
-            game.update_counter(k, *v);
-        }
+    /// Returns every statement in this block's body in the order they were written, including those in every branch of its sections.
+    ///
+    /// Each section comes just before the statements of its branches, and each option just before its effects.
+    ///
+    /// ```
+    /// # use intfic::parse_file::parse_str;
+    /// # use intfic::story_block::Statement;
+    /// let blocks = parse_str("inline.txt", ":- start\nif- has_key\n=- door_open = true\nendif-").unwrap();
+    /// let statements: Vec<&Statement> = blocks[0].statements();
+    ///
+    /// assert_eq!(statements.len(), 2);
+    /// assert_eq!(statements[1], &Statement::SetFlag(String::from("door_open"), true));
+    /// ```
+    pub fn statements(&self) -> Vec<&Statement> {
+        let mut statements: Vec<&Statement> = Vec::new();
+        walk(&self.body, &mut statements);
+        statements
     }
 
-    // Presents a filtered, ordered list of options for the player to choose from, and facitates the player making a choice
-    //
-    // Filters out any options that have conditions which are not satisfied in our GameState.
-    // Once the options are presented, waits for user input and then checks that input against the given options.
-    // If any match, start that option's "result" block or file. If non match, ask for another input.
-    fn present_options(&self, game: &mut GameState, blocks: &[StoryBlock]) {
-        let options: &Vec<Choice> = &filter_options(&self.options, game);
-        let num_options = options.len();
-
-        if num_options == 0 {
-            return;
-        } else if num_options == 1 {
-            play_next(&options[0].result, game, blocks);
-            return;
-        }
+    /// Returns the options of this block that either have no condition or have a condition that returns true in our GameState.
+    ///
+    /// Options in sections are only included if they're in the branch that passes,
+    /// and `[once]` and `[fallback]` options are left out as described in [available_choices](fn.available_choices.html).
+    ///
+    /// ```
+    /// # use intfic::expr::Expr;
+    /// # use intfic::game_state::GameState;
+    /// # use intfic::story_block::{Choice, Statement, StoryBlock};
+    /// let mut game: GameState = GameState::new("Test GameState");
+    /// let mut block: StoryBlock = StoryBlock::new(String::from("test_block"));
+    /// block.body.push(Statement::Choice(Choice {
+    ///     text: String::from("Open the door"),
+    ///     condition: Some(Expr::parse("has_key").unwrap()),
+    ///     typed: String::from("open"),
+    ///     result: String::from("open_door"),
+    ///     ..Choice::default()
+    /// }));
+    ///
+    /// assert!(block.available_options(&game).is_empty());
+    /// game.set_flag("has_key", true);
+    /// assert_eq!(block.available_options(&game)[0].text, String::from("Open the door"));
+    /// ```
+    pub fn available_options(&self, game: &GameState) -> Vec<Choice> {
+        let mut offered: Vec<&Choice> = Vec::new();
+        offer(&self.body, game, &mut offered);
+        available_choices(offered, game)
+    }
+}
 
-        let mut num: i32 = 1;
-        for choice in options {
-            choice.present(num, game);
-            num += 1;
+// Carries out the given statements and the branches of sections that pass, skipping questions and options.
+fn carry_out(statements: &[Statement], game: &mut GameState, events: &mut Vec<Event>) {
+    for statement in statements.iter() {
+        if let Next::Enter(branch) = statement.execute(game, events) {
+            carry_out(branch, game, events);
         }
-        println!();
-
-        let mut valid_choice: bool = false;
-        while !valid_choice {
-            if let Some(input) = get_input(game) {
-                if input.is_empty() {
-                    continue;
-                }
+    }
+}
 
-                let mut num = 1;
-                for choice in options {
-                    if choice.match_option(&input, num) {
-                        play_next(&choice.result, game, blocks);
-                        valid_choice = true;
-                        break;
-                    }
-                    num += 1;
+// Reads the lines of text in the given statements and the branches of sections that pass, without applying any effects.
+fn read_lines(statements: &[Statement], game: &GameState, events: &mut Vec<Event>) {
+    for statement in statements.iter() {
+        match statement {
+            Statement::Text(line) => read_line(line, game, events),
+            Statement::Conditional(conditional) => {
+                if let Some(line) = conditional.pick(game) {
+                    read_line(line, game, events);
                 }
-
-                if !valid_choice {
-                    println!("I didn't understand that.");
+            }
+            Statement::Section(section) => read_lines(section.branch(game), game, events),
+            Statement::Sequence(sequence) => {
+                let reference: String = sequence.reference(game);
+                let read: u32 = game.sequences.get(&reference).copied().unwrap_or(0);
+                if let Some(line) = sequence.line(read.saturating_sub(1), &reference, game.seed) {
+                    read_line(line, game, events);
                 }
-            } else {
-                break;
             }
+            _ => (),
         }
     }
 }
 
-// Given a string with a proper integer comparason conditional, parse and return the result of that conditional.
-fn check_counter(cond: &str, game: &GameState) -> bool {
-    let mut cond_split = cond.split(' ');
-    let count_name: &str = cond_split.nth(1).unwrap();
-    let count_amount = game.get_counter(count_name);
-
-    match cond_split.next().unwrap() {
-        "<" => count_amount < cond_split.next().unwrap().parse::<i32>().unwrap(),
-        "<=" => count_amount <= cond_split.next().unwrap().parse::<i32>().unwrap(),
-        "==" => count_amount == cond_split.next().unwrap().parse::<i32>().unwrap(),
-        ">=" => count_amount >= cond_split.next().unwrap().parse::<i32>().unwrap(),
-        ">" => count_amount > cond_split.next().unwrap().parse::<i32>().unwrap(),
-        _ => false,
-    }
-}
-
-// Print out a line according to conditionals or colors prefixing it.
-//
-// Checks if a line has a conditional, and on displays the "Then" portion of the line if it passes.
-// Lines of text may also have an optional "Else" portion if using a conditional.
-// If a line apsses, ti is then checked for color indicators, and is sent to write_out with the appropriate Color enum.
-fn read_line(line: &str, game: &GameState) {
-    if line.starts_with("?-") {
-        let mut cond_split = line.split(" => ");
-
-        if game.get_flag(&(cond_split.next().unwrap())[3..]) {
-            read_line(&String::from(cond_split.next().unwrap()), game);
-        } else if let Some(else_line) = cond_split.nth(1) {
-            read_line(&String::from(else_line), game);
-        }
-    } else if line.starts_with("#-") {
-        let mut line_split = line.split(" => ");
-
-        if check_counter(line_split.next().unwrap(), game) {
-            read_line(&String::from(line_split.next().unwrap()), game);
-        } else if let Some(else_line) = line_split.nth(1) {
-            read_line(&String::from(else_line), game);
+// Collects the options in the given statements and the branches of sections that pass.
+fn offer<'a>(statements: &'a [Statement], game: &GameState, offered: &mut Vec<&'a Choice>) {
+    for statement in statements.iter() {
+        match statement {
+            Statement::Choice(choice) => offered.push(choice),
+            Statement::Section(section) => offer(section.branch(game), game, offered),
+            _ => (),
         }
-    
-    } else if line.starts_with("-b ") {
-        type_text(&line[3..], Color::Blue, false);
-    } else if line.starts_with("-c ") {
-        type_text(&line[3..], Color::Cyan, false);
-    } else if line.starts_with("-g ") {
-        type_text(&line[3..], Color::Green, false);
-    } else if line.starts_with("-p ") {
-        type_text(&line[3..], Color::Purple, false);
-    }  else if line.starts_with("-r ") {
-        type_text(&line[3..], Color::Red, false);
-    } else if line.starts_with("-y ") {
-        type_text(&line[3..], Color::Yellow, false);
-    } else if line.starts_with("  ") { // Indicates a question
-        println!();
-        type_text(&line, Color::Cyan, false);
-    } else {
-        type_text(&line, Color::White, false);
     }
 }
 
-// Filter a list of options to only include those who either have no condition or have a condition that returns true in our GameState
-fn filter_options(options: &[Choice], game: &GameState) -> Vec<Choice> {
-    let mut filtered: Vec<Choice> = Vec::new();
-
-    for choice in options.iter() {
-        if choice.text.starts_with("?-") {
-            let mut cond_split = choice.text.split(" => ");
+// Collects every statement in the given statements, in every branch of their sections, and in the effects of their options, in the order they were written.
+fn walk<'a>(statements: &'a [Statement], found: &mut Vec<&'a Statement>) {
+    for statement in statements.iter() {
+        found.push(statement);
 
-            if game.get_flag(&(cond_split.next().unwrap())[3..]) {
-                filtered.push(Choice {
-                    text: String::from(cond_split.next().unwrap()),
-                    typed: choice.typed.clone(),
-                    result: choice.result.clone(),
-                })
-            }
-        } else if choice.text.starts_with("#-") {
-            let mut choice_split = choice.text.split(" => ");
-
-            if check_counter(choice_split.next().unwrap(), game) {
-                filtered.push(Choice {
-                    text: String::from(choice_split.next().unwrap()),
-                    typed: choice.typed.clone(),
-                    result: choice.result.clone(),
-                })
+        match statement {
+            Statement::Section(section) => {
+                for (_, branch) in section.branches.iter() {
+                    walk(branch, found);
+                }
+                walk(&section.otherwise, found);
             }
-        } else {
-            filtered.push(choice.clone());
+            Statement::Choice(choice) => walk(&choice.effects, found),
+            _ => (),
         }
     }
-
-    filtered
 }
 
-/// Starts reading the first block in the given Vec\<StoryBlock>.
-/// 
-/// ```no_run
-/// # use intfic::game_state::GameState;
-/// # use intfic::parse_file::load_file;
-/// # use intfic::story_block::start_blocks;
-/// let mut game: GameState = GameState::new("Test GameState");
-/// 
-/// if let Some(loaded_blocks) = load_file("example_1.txt", &mut game) {
-///     start_blocks(&loaded_blocks, &mut game);
-/// }
-/// ```
-pub fn start_blocks(blocks: &[StoryBlock], game: &mut GameState) {
-    blocks[0].read(game, blocks);
-}
-
-/// Starts reading the block with the given name in the given Vec\<StoryBlock>.
-/// 
-/// If no block matching the name is found, Print a debug message stating so.
-/// 
-/// ```no_run
-/// # use intfic::game_state::GameState;
-/// # use intfic::parse_file::load_file;
-/// # use intfic::story_block::start_block;
-/// let mut game: GameState = GameState::new("Test GameState");
-/// 
-/// if let Some(loaded_blocks) = load_file("example_1.txt", &mut game) {
-///     start_block(String::from("flag_example"), &loaded_blocks, &mut game);
-/// }
-/// ```
-pub fn start_block(name: String, blocks: &[StoryBlock], game: &mut GameState) {
-    if let Some(block) = find_block(&name[..], blocks) {
-        block.read(game, blocks);
+// Turn a line into events according to colors prefixing it.
+//
+// The line is checked for color indicators, and is added as a Line event with the appropriate Color enum.
+fn read_line(line: &str, game: &GameState, events: &mut Vec<Event>) {
+    if let Some(text) = line.strip_prefix("-b ") {
+        events.push(fill(text, Color::Blue, game));
+    } else if let Some(text) = line.strip_prefix("-c ") {
+        events.push(fill(text, Color::Cyan, game));
+    } else if let Some(text) = line.strip_prefix("-g ") {
+        events.push(fill(text, Color::Green, game));
+    } else if let Some(text) = line.strip_prefix("-p ") {
+        events.push(fill(text, Color::Purple, game));
+    } else if let Some(text) = line.strip_prefix("-r ") {
+        events.push(fill(text, Color::Red, game));
+    } else if let Some(text) = line.strip_prefix("-y ") {
+        events.push(fill(text, Color::Yellow, game));
+    } else if line.starts_with("  ") { // Indicates a question
+        events.push(Event::Break);
+        events.push(fill(line, Color::Cyan, game));
     } else {
-        print_debug(format!("No block found with the name {}", name));
+        events.push(fill(line, Color::White, game));
     }
 }
 
-// Searches the given list fo blocks for one that matches the given name, returning Some(StoryBlock) if successful.
-fn find_block<'a>(name: &str, blocks: &'a [StoryBlock]) -> Option<&'a StoryBlock> {
-    for block in blocks {
-        if block.name == *name {
-            return Some(block);
-        }
-    }
-    None
-}
-
-// Plays the next StoryBlock or Story file based on the given name.
-fn play_next(name: &str, game: &mut GameState, blocks: &[StoryBlock]) {
-    if name.ends_with(".txt") {
-        if let Some(next_blocks) = load_file(name, game) {
-            start_blocks(&next_blocks, game);
-        }
-    } else if let Some(next_block) = find_block(name, blocks) {
-        game.set_flag("saved", false);
-        next_block.read(game, blocks);
-    } else {
-        print_debug(format!("Can't find StoryBlock: {}", name));
+// Fills in the placeholders of a line of text, turning it into a Line event with the given Color.
+//
+// If a placeholder can't be filled in, the line is replaced with a red description of the problem so the author can spot it.
+fn fill(text: &str, color: Color, game: &GameState) -> Event {
+    match interpolate(text, game) {
+        Ok(filled) => Event::Line(filled, color),
+        Err(e) => Event::Line(format!("Couldn't show \"{}\": {}", text, e), Color::Red),
     }
 }
@@ -0,0 +1,192 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::game_state::GameState;
+
+/// A problem found while filling in the placeholders of a line of text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateError {
+    /// The byte offset within the text the problem was found at.
+    pub offset: usize,
+    /// A description of what went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for TemplateError {}
+
+// A placeholder found in a line of text, and where it starts.
+struct Placeholder<'a> {
+    offset: usize,
+    name: &'a str,
+    align: Option<char>,
+    zero: bool,
+    width: usize,
+    forms: Option<(&'a str, &'a str)>,
+}
+
+// The value of a variable, from whichever of our GameState's environments it was found in.
+enum Value<'a> {
+    Text(&'a str),
+    Int(i32),
+    Bool(bool),
+}
+
+/// Replaces the placeholders in a line of text with the current values of variables in our GameState.
+///
+/// * `{name}` is replaced with the value of the string, counter, or flag with that name, looked for in that order.
+/// * `{name:5}` pads the value to at least 5 characters. Numbers are aligned right and everything else left,
+///   unless the width is preceded by `<`, `>`, or `^` to align left, right, or center. `{name:03}` pads a number with zeros.
+/// * `{name|one|many}` is replaced with "one" if the counter is 1, the flag is true, or the string isn't empty, and with "many" otherwise.
+/// * `{{` and `}}` are replaced with `{` and `}`.
+///
+/// Returns a TemplateError if a placeholder is malformed, or refers to a variable that isn't in our GameState.
+///
+/// ```
+/// # use intfic::game_state::GameState;
+/// # use intfic::template::interpolate;
+/// let mut game: GameState = GameState::new("Test GameState");
+/// game.set_string("player_name", "Sam");
+/// game.update_counter("gold", 1);
+///
+/// assert_eq!(interpolate("Hi {player_name}, you have {gold} {gold|coin|coins}.", &game).unwrap(), "Hi Sam, you have 1 coin.");
+/// assert_eq!(interpolate("[{score:>4}] {{braces}}", &game).unwrap(), "[   0] {braces}");
+/// assert_eq!(interpolate("{gems}", &game).unwrap_err().message, "unknown variable `gems`");
+/// ```
+pub fn interpolate(text: &str, game: &GameState) -> Result<String, TemplateError> {
+    let mut filled: String = String::new();
+    let mut rest: &str = text;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        filled.push_str(&rest[..start]);
+        let offset: usize = text.len() - rest.len() + start;
+
+        if rest[start..].starts_with("{{") || rest[start..].starts_with("}}") {
+            filled.push_str(&rest[start..start + 1]);
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        let (placeholder, len) = parse_placeholder(&rest[start..], offset)?;
+        filled.push_str(&render(&placeholder, game)?);
+        rest = &rest[start + len..];
+    }
+
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+/// Returns the names of the variables the placeholders in a line of text refer to, in order.
+///
+/// Returns a TemplateError pointing at the first malformed placeholder, if any.
+///
+/// ```
+/// # use intfic::template::variables;
+/// assert_eq!(variables("{player_name} has {gold:3} {gold|coin|coins}").unwrap(), vec!["player_name", "gold", "gold"]);
+/// assert_eq!(variables("{{not a placeholder}}").unwrap(), Vec::<&str>::new());
+/// assert_eq!(variables("Oops {gold").unwrap_err().offset, 5);
+/// ```
+pub fn variables(text: &str) -> Result<Vec<&str>, TemplateError> {
+    let mut names: Vec<&str> = Vec::new();
+    let mut rest: &str = text;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        let offset: usize = text.len() - rest.len() + start;
+
+        if rest[start..].starts_with("{{") || rest[start..].starts_with("}}") {
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        let (placeholder, len) = parse_placeholder(&rest[start..], offset)?;
+        names.push(placeholder.name);
+        rest = &rest[start + len..];
+    }
+
+    Ok(names)
+}
+
+// Parses the placeholder at the start of the given text, returning it and its length including braces.
+//
+// The offset is where the text starts within its line, so errors can point at the right place.
+fn parse_placeholder(text: &str, offset: usize) -> Result<(Placeholder<'_>, usize), TemplateError> {
+    let error = |at: usize, message: String| TemplateError { offset: offset + at, message };
+
+    if text.starts_with('}') {
+        return Err(error(0, String::from("unmatched `}`, use `}}` to show a brace")));
+    }
+    let end: usize = text.find('}').ok_or_else(|| error(0, String::from("unclosed `{`, use `{{` to show a brace")))?;
+    let content: &str = &text[1..end];
+
+    let mut forms_split = content.split('|');
+    let head: &str = forms_split.next().unwrap_or_default();
+    let forms: Option<(&str, &str)> = match (forms_split.next(), forms_split.next(), forms_split.next()) {
+        (None, _, _) => None,
+        (Some(one), Some(many), None) => Some((one, many)),
+        _ => return Err(error(1, format!("expected `{{name|one|many}}`, found `{{{}}}`", content))),
+    };
+
+    let (name, spec) = match head.split_once(':') {
+        Some((name, spec)) => (name, spec),
+        None => (head, ""),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(error(1, format!("expected a variable name, found `{}`", name)));
+    }
+
+    let align: Option<char> = spec.chars().next().filter(|c| "<>^".contains(*c));
+    let digits: &str = &spec[align.map_or(0, char::len_utf8)..];
+    let width: usize = match digits {
+        "" => 0,
+        _ => digits.parse().map_err(|_| {
+            error(name.len() + 2, format!("expected a width like `5`, `>5`, or `05` after `:`, found `{}`", spec))
+        })?,
+    };
+
+    let placeholder = Placeholder { offset, name, align, zero: digits.starts_with('0'), width, forms };
+    Ok((placeholder, end + 1))
+}
+
+// Fills in a single placeholder with the value of its variable in our GameState.
+fn render(placeholder: &Placeholder, game: &GameState) -> Result<String, TemplateError> {
+    let value: Value = if let Some(text) = game.strings.get(placeholder.name) {
+        Value::Text(text)
+    } else if let Some(n) = game.counters.get(placeholder.name) {
+        Value::Int(*n)
+    } else if let Some(b) = game.flags.get(placeholder.name) {
+        Value::Bool(*b)
+    } else {
+        return Err(TemplateError {
+            offset: placeholder.offset,
+            message: format!("unknown variable `{}`", placeholder.name),
+        });
+    };
+
+    let width: usize = placeholder.width;
+    let text: String = match (placeholder.forms, value) {
+        (Some((one, many)), value) => {
+            let singular: bool = match value {
+                Value::Text(text) => !text.is_empty(),
+                Value::Int(n) => n == 1,
+                Value::Bool(b) => b,
+            };
+            String::from(if singular { one } else { many })
+        }
+        (None, Value::Int(n)) if placeholder.zero => return Ok(format!("{:0width$}", n, width = width)),
+        (None, Value::Int(n)) if placeholder.align.is_none() => return Ok(format!("{:>width$}", n, width = width)),
+        (None, Value::Int(n)) => n.to_string(),
+        (None, Value::Bool(b)) => b.to_string(),
+        (None, Value::Text(text)) => String::from(text),
+    };
+
+    Ok(match placeholder.align {
+        Some('>') => format!("{:>width$}", text, width = width),
+        Some('^') => format!("{:^width$}", text, width = width),
+        _ => format!("{:<width$}", text, width = width),
+    })
+}
@@ -1,10 +1,23 @@
-use std::collections::HashMap;
+use bincode::Options;
 
+use crate::embed::*;
+use crate::engine::*;
+use crate::expr::*;
+use crate::frontend::*;
 use crate::game_state::*;
+use crate::lint::*;
+use crate::manifest::*;
 use crate::parse_file::*;
+use crate::parse_input::*;
+use crate::save::*;
+use crate::store::*;
+use crate::story::*;
 use crate::story_block::*;
+use crate::template::*;
+use crate::write_out::Color;
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_gamestate() {
     let mut test_state: GameState = GameState::new("Test_Game");
 
@@ -20,42 +33,1283 @@ fn test_gamestate() {
     assert_eq!(test_state.get_counter("score"), 0);
     test_state.add_score(50);
     assert_eq!(test_state.get_counter("score"), 50);
+
+    test_state.check_game_over();
+    assert!(!test_state.quitting);
+    test_state.set_flag("game_over", true);
+    test_state.check_game_over();
+    assert!(test_state.quitting);
 }
 
 #[test]
 fn test_storyblocks() {
     let mut test_state: GameState = GameState::new("Test_Story");
 
-    if let Some(test_blocks) = load_file("test.txt", &mut test_state) {
+    if let Ok(test_blocks) = load_file("test.txt", &mut test_state) {
         assert_eq!(test_blocks[1].name, "test_1");
 
         assert_eq!(
-            test_blocks[1].text,
+            test_blocks[1].body,
             vec!(
-                String::from(""),
-                String::from("You picked test 1!"),
-                String::from("?- impossible_condition => this should never be seen"),
-                String::from("?- test_condition => this should always be seen"),
-                String::from("")
+                Statement::Text(String::from("")),
+                Statement::Text(String::from("You picked test 1!")),
+                Statement::Conditional(Conditional {
+                    condition: Expr::parse("impossible_condition").unwrap(),
+                    then: String::from("this should never be seen"),
+                    otherwise: None,
+                }),
+                Statement::Conditional(Conditional {
+                    condition: Expr::parse("test_condition").unwrap(),
+                    then: String::from("this should always be seen"),
+                    otherwise: None,
+                }),
+                Statement::SetFlag(String::from("test_condition"), false),
+                Statement::Choice(Choice {
+                    text: String::default(),
+                    typed: String::default(),
+                    result: String::from("test_5"),
+                    ..Choice::default()
+                }),
+                Statement::Text(String::from(""))
             )
         );
 
-        assert_eq!(
-            test_blocks[1].options,
-            vec!(Choice {
-                text: String::default(),
-                typed: String::default(),
-                result: String::from("test_5"),
+        let mut events: Vec<Event> = Vec::new();
+        test_state.set_flag("test_condition", true);
+        test_blocks[1].read(&mut test_state, &mut events);
+        assert!(events.contains(&Event::Line(String::from("this should always be seen"), Color::White)));
+        assert!(!test_state.get_flag("test_condition"));
+    } else {
+        panic!("Couldn't load test.txt into StoryBlocks");
+    }
+}
+
+#[test]
+fn test_engine() {
+    let mut test_state: GameState = GameState::new("Test_Engine");
+    test_state.set_progress("test.txt", "start");
+
+    let mut engine: Engine = Engine::new(test_state);
+    let output = engine.start();
+
+    assert!(output.events.contains(&Event::Line(String::from("this is all in yellow"), Color::Yellow)));
+    assert_eq!(
+        output.events.last(),
+        Some(&Event::Options(vec!(
+            String::from("Test Option 1"),
+            String::from("Test Option 2"),
+            String::from("Test Option 4"),
+        )))
+    );
+    assert!(!engine.is_finished());
+
+    let output = engine.step("not an option");
+    assert_eq!(output.events, vec!(Event::Line(String::from("I didn't understand that."), Color::White)));
+
+    let output = engine.step("option1");
+    assert!(output.events.contains(&Event::Line(String::from("this should always be seen"), Color::White)));
+    assert!(!output.events.contains(&Event::Line(String::from("this should never be seen"), Color::White)));
+    assert_eq!(output.events.last(), Some(&Event::End));
+    assert!(engine.is_finished());
+    assert_eq!(engine.game.progress, (String::from("test.txt"), String::from("end")));
+
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- start\n*- Wander -> wander -> a\n*- Count -> count -> counting\n:- a\n-> b\n:- b\n-> a\n:- counting\n+- laps + 1\n-> ?- laps < 3 => counting => done\n:- done\nDone.",
+    )]);
+    let new_state = || -> GameState {
+        let mut test_state: GameState = GameState::new("Test_Engine_Cycle");
+        test_state.set_progress("test.txt", "start");
+        test_state
+    };
+
+    let mut engine: Engine = Engine::with_loader(new_state(), loader.clone());
+    engine.start();
+    let events: Vec<Event> = engine.step("count").events;
+    assert!(events.contains(&Event::Line(String::from("Done."), Color::White)));
+    assert_eq!(engine.game.get_counter("laps"), 3);
+
+    let mut engine: Engine = Engine::with_loader(new_state(), loader);
+    engine.start();
+    let events: Vec<Event> = engine.step("wander").events;
+    assert!(events.iter().any(|event| matches!(event, Event::Line(line, Color::Red) if line.contains("may go in a circle"))));
+    assert_eq!(events.last(), Some(&Event::End));
+    assert!(engine.is_finished());
+}
+
+#[test]
+fn test_resume_lone_option() {
+    let loader: Loader = Loader::from_files(vec![("test.txt", ":- start\nA lone path.\n\n*- [once] Go -> go -> next -> +- gold + 1\n\n:- next\nThe end.")]);
+    let mut test_state: GameState = GameState::new("Test_Resume");
+    test_state.set_progress("test.txt", "start");
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+
+    let output = engine.resume();
+    assert_eq!(output.events.last(), Some(&Event::Options(vec![String::from("Go")])));
+    assert_eq!(engine.resume(), output);
+    assert_eq!(engine.game.get_counter("gold"), 0);
+    assert_eq!(engine.game.progress_ref(), "test.txt#start");
+
+    engine.step("1");
+    assert_eq!(engine.game.get_counter("gold"), 1);
+    assert_eq!(engine.game.progress_ref(), "test.txt#next");
+}
+
+#[test]
+fn test_quit() {
+    let mut test_state: GameState = GameState::new("Test_Quit");
+    test_state.set_progress("test.txt", "start");
+    let mut engine: Engine = Engine::new(test_state);
+    let mut mock: Mock = Mock::new(&["exit", "no", "option1"]);
+    engine.run(&mut mock);
+
+    assert!(engine.game.quitting);
+    assert!(engine.is_finished());
+    assert_eq!(mock.transcript.last(), Some(&String::from("See you next time!")));
+    assert_eq!(engine.game.progress_ref(), "test.txt#start");
+
+    let mut game: GameState = GameState::new("Test_Quit");
+    game.set_flag("saved", true);
+    assert_eq!(check_keywords(String::from("quit"), &mut game, &mut Mock::new(&[])), None);
+    assert!(game.quitting);
+}
+
+#[test]
+fn test_saved_flag() {
+    let loader: Loader = Loader::from_files(vec![
+        ("test.txt", ":- start\nHere.\n\n*- Same file -> same -> there\n*- Other file -> other -> other.txt#start\n\n:- there\nThere."),
+        ("other.txt", ":- start\nElsewhere."),
+    ]);
+
+    for (input, progress) in [("same", "test.txt#there"), ("other", "other.txt#start")] {
+        let mut test_state: GameState = GameState::new("Test_Saved_Flag");
+        test_state.set_progress("test.txt", "start");
+        let mut engine: Engine = Engine::with_loader(test_state, loader.clone());
+        engine.start();
+        engine.game.set_flag("saved", true);
+
+        engine.step(input);
+        assert_eq!(engine.game.progress_ref(), progress);
+        assert!(!engine.game.get_flag("saved"));
+    }
+}
+
+#[test]
+fn test_mock_frontend() {
+    let mut test_state: GameState = GameState::new("Test_Frontend");
+    test_state.set_progress("test.txt", "start");
+
+    let mut mock: Mock = Mock::new(&["huh", "option4", "continue"]);
+    test_state.start(&mut mock);
+
+    assert!(mock.inputs.is_empty());
+    assert!(mock.transcript.contains(&String::from("1) Test Option 1")));
+    assert!(mock.transcript.contains(&String::from("3) Test Option 4")));
+    assert!(mock.transcript.contains(&String::from("I didn't understand that.")));
+    assert!(mock.transcript.contains(&String::from("I just added 50 to your score!")));
+    assert!(mock.transcript.contains(&String::from("This is test 5!")));
+    assert_eq!(test_state.progress, (String::from("test.txt"), String::from("end")));
+}
+
+#[test]
+fn test_parse_errors() {
+    let mut test_state: GameState = GameState::new("Test_Errors");
+
+    assert_eq!(
+        load_file("test_malformed.txt", &mut test_state),
+        Err(ParseError::Syntax {
+            filename: String::from("test_malformed.txt"),
+            line: 4,
+            column: 18,
+            text: String::from("=- broken_flag = maybe"),
+            message: String::from("expected `true` or `false`, found `maybe`"),
+        })
+    );
+
+    match load_file("not_a_file.txt", &mut test_state) {
+        Err(ParseError::Io { filename, .. }) => assert_eq!(filename, String::from("not_a_file.txt")),
+        other => panic!("Expected an Io error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_lint() {
+    let mut test_state: GameState = GameState::new("Test_Lint");
+    test_state.set_progress("test.txt", "start");
+
+    let report = check(&test_state, &Loader::default());
+    assert!(!report.has_errors());
+    assert_eq!(report.files, vec!(String::from("test.txt")));
+    assert!(report.problems.contains(&Problem {
+        severity: Severity::Warning,
+        kind: ProblemKind::UnknownPrefix,
+        file: String::from("test.txt"),
+        line: Some(34),
+        block: Some(String::from("test_4")),
+        message: String::from("`+=` isn't a known prefix, so this line will be shown as text"),
+    }));
+
+    test_state.set_progress("example_1.txt", "start");
+    let report = check(&test_state, &Loader::default());
+    assert!(report.has_errors());
+    assert!(report
+        .problems
+        .iter()
+        .any(|problem| problem.kind == ProblemKind::MissingTarget && problem.message.contains("time_fix")));
+}
+
+#[test]
+fn test_loader() {
+    let loader: Loader = Loader::from_files(vec![
+        ("chapter_1.txt", ":- start\nChapter one.\n-> chapter_2.txt"),
+        ("chapter_2.txt", ":- start\nChapter two.\n*- Again -> again -> chapter_1.txt\n*- Stop -> stop -> end\n\n:- end\nThe end."),
+    ]);
+
+    let blocks: Vec<StoryBlock> = loader.load("chapter_2.txt").unwrap();
+    assert_eq!(blocks, parse_reader("chapter_2.txt", loader.read("chapter_2.txt").unwrap().join("\n").as_bytes()).unwrap());
+
+    let mut test_state: GameState = GameState::new("Test_Loader");
+    test_state.set_progress("chapter_1.txt", "start");
+
+    let mut mock: Mock = Mock::new(&["again", "stop"]);
+    let mut engine: Engine = Engine::with_loader(test_state, loader.clone());
+    engine.run(&mut mock);
+
+    assert_eq!(mock.transcript.iter().filter(|line| *line == "Chapter two.").count(), 2);
+    assert!(mock.transcript.contains(&String::from("The end.")));
+    assert_eq!(engine.game.progress, (String::from("chapter_2.txt"), String::from("end")));
+    assert!(!check(&engine.game, &loader).has_errors());
+}
+
+#[test]
+fn test_embed() {
+    let loader: Loader = crate::include_story!("../resources", ["test.txt"]);
+    let mut test_state: GameState = GameState::new("Test_Embed");
+    assert_eq!(loader.load("test.txt"), load_file("test.txt", &mut test_state));
+
+    let story_dir = std::env::temp_dir().join("intfic_test_embed");
+    std::fs::create_dir_all(&story_dir).unwrap();
+    std::fs::copy("resources/test.txt", story_dir.join("test.txt")).unwrap();
+    std::fs::write(story_dir.join("notes.md"), "Not a story file.").unwrap();
+
+    let bundled = write_bundle(&story_dir, &story_dir.join("bundle.rs")).unwrap();
+    let bundle: String = std::fs::read_to_string(story_dir.join("bundle.rs")).unwrap();
+
+    assert_eq!(bundled, vec![story_dir.join("test.txt")]);
+    assert!(bundle.starts_with("&[\n    (\"test.txt\", include_str!("));
+
+    let broken_dir = std::env::temp_dir().join("intfic_test_embed_broken");
+    std::fs::create_dir_all(&broken_dir).unwrap();
+    std::fs::write(broken_dir.join("broken.txt"), ":- start\n+- score plus lots").unwrap();
+    match write_bundle(&broken_dir, &broken_dir.join("bundle.rs")) {
+        Err(ParseError::Syntax { filename, line, .. }) => assert_eq!((filename, line), (String::from("broken.txt"), 2)),
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+
+    let out_dir = std::env::temp_dir().join("intfic_test_embed_out");
+    let bundle_path = bundle_story_in(&story_dir, &out_dir).unwrap();
+    assert_eq!(bundle_path, out_dir.join("intfic_story").join(bundle_name(&story_dir)));
+    assert_eq!(std::fs::read_to_string(&bundle_path).unwrap(), bundle);
+    std::fs::create_dir_all("target/intfic_test_embed").unwrap();
+    std::fs::copy("resources/test.txt", "target/intfic_test_embed/test.txt").unwrap();
+    let nested_path = bundle_story_in(std::path::Path::new("target/intfic_test_embed"), &out_dir).unwrap();
+    assert_eq!(nested_path, out_dir.join("intfic_story").join("target_intfic_test_embed.rs"));
+}
+
+#[test]
+fn test_manifest() {
+    let manifest: Manifest = Manifest::load(&Loader::default()).unwrap();
+    assert_eq!(manifest.title, String::from("Interactive Fiction Title"));
+    assert_eq!(manifest.files, vec![String::from("example_1.txt"), String::from("example_2.txt")]);
+
+    let test_state: GameState = GameState::from_manifest(&manifest);
+    assert_eq!(test_state.progress, (String::from("example_1.txt"), String::from("start")));
+
+    let loader: Loader = Loader::from_files(vec![
+        ("story.ron", "(\n    title: \"Test Story\",\n    start: (\"test.txt\", \"start\"),\n    flags: { \"test_flag\": true },\n)"),
+        ("test.txt", ":- start\n?- test_flag => The flag was set!"),
+    ]);
+    let mut engine: Engine = Engine::from_manifest(loader).unwrap();
+    assert_eq!(engine.start().events[0], Event::Line(String::from("The flag was set!"), Color::White));
+
+    match Manifest::parse("(\n    title: \"Test Story\",\n    start: \"test.txt\",\n)") {
+        Err(ParseError::Syntax { filename, line, .. }) => assert_eq!((filename, line), (String::from("story.ron"), 3)),
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+    assert!(Engine::from_manifest(Loader::from_files(vec![("test.txt", ":- start")])).is_err());
+}
+
+#[test]
+fn test_story() {
+    let loader: Loader = Loader::from_files(vec![
+        ("start.txt", ":- start\nHello!\n*- Stay -> stay -> stay\n*- Leave -> leave -> next.txt\n:- stay\nStill here.\n:- stay\nNever read."),
+        ("next.txt", ":- next\nGoodbye!\n-> start.txt"),
+        ("unreached.txt", ":- unreached"),
+    ]);
+    let story: Story = Story::load(&loader, &["start.txt"]).unwrap();
+
+    let mut files: Vec<&str> = story.files().collect();
+    files.sort_unstable();
+    assert_eq!(files, vec!["next.txt", "start.txt"]);
+    assert_eq!(story.get("start.txt", "stay").unwrap().text(), vec!["Still here."]);
+    assert_eq!(story.first("next.txt").unwrap().name, String::from("next"));
+    assert_eq!(story.blocks("start.txt").unwrap().len(), 3);
+    assert!(story.get("next.txt", "stay").is_none());
+
+    // Once the story is preloaded, moving between files never goes back to the loader.
+    let mut test_state: GameState = GameState::new("Test_Story");
+    test_state.set_progress("start.txt", "");
+
+    let mut engine: Engine = Engine::with_loader(test_state, Loader::from_files(Vec::<(String, String)>::new()));
+    engine.story = story;
+    engine.start();
+    let output = engine.step("leave");
+
+    assert_eq!(output.events[0], Event::Line(String::from("Goodbye!"), Color::White));
+    assert_eq!(engine.game.progress, (String::from("start.txt"), String::from("start")));
+}
+
+#[test]
+fn test_block_references() {
+    let loader: Loader = Loader::from_files(vec![
+        ("start.txt", ":- start\nHello!\n*- Ambush -> ambush -> next.txt#ambush\n*- Missing -> missing -> next.txt#missing"),
+        ("next.txt", ":- next\nNever read.\n:- ambush\nSurprise!\n-> start.txt#start"),
+    ]);
+
+    let mut test_state: GameState = GameState::new("Test_References");
+    test_state.set_progress_ref("start.txt#start");
+    assert_eq!(test_state.progress_ref(), String::from("start.txt#start"));
+
+    let mut engine: Engine = Engine::with_loader(test_state, loader.clone());
+    engine.start();
+    let output = engine.step("ambush");
+    assert_eq!(output.events[0], Event::Line(String::from("Surprise!"), Color::White));
+    assert_eq!(engine.game.progress_ref(), String::from("start.txt#start"));
+
+    let report = check(&engine.game, &loader);
+    assert_eq!(report.problems.len(), 2);
+    assert_eq!(report.problems[0].kind, ProblemKind::MissingTarget);
+    assert_eq!(report.problems[0].line, Some(4));
+    assert_eq!(report.problems[1].kind, ProblemKind::UnreachableBlock);
+    assert_eq!(report.problems[1].block, Some(String::from("next")));
+
+    match parse_str("bad.txt", ":- start\n-> chapter2#ambush") {
+        Err(ParseError::Syntax { column, .. }) => assert_eq!(column, 12),
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+    assert!(parse_str("bad.txt", ":- start\n*- Go -> go -> chapter2.txt#").is_err());
+}
+
+#[test]
+fn test_expressions() {
+    let mut test_state: GameState = GameState::new("Test_Expressions");
+    test_state.set_flag("has_key", true);
+    test_state.update_counter("strength", 12);
+    test_state.update_counter("enemy_strength", 5);
+
+    let expr: Expr = Expr::parse("(has_key and not door_open) || strength > enemy_strength * 3").unwrap();
+    assert!(expr.is_true(&test_state));
+    assert_eq!(expr.flags(), vec!["door_open", "has_key"]);
+    assert_eq!(expr.counters(), vec!["enemy_strength", "strength"]);
+
+    assert!(!Expr::parse("strength - 12").unwrap().is_true(&test_state));
+    assert_eq!(Expr::parse("-strength / 5 + (has_key == true)").unwrap().value(&test_state), -1);
+    assert_eq!(Expr::parse("door_open != false").unwrap().flags(), vec!["door_open"]);
+    assert_eq!(Expr::parse("strength / (enemy_strength - 5)").unwrap().value(&test_state), 0);
+
+    let blocks = parse_str(
+        "test.txt",
+        ":- start\n?- has_key and strength > enemy_strength => Strong! => Weak.\n*- #- not has_key => Break the door -> break -> break\n*- Leave -> leave -> leave",
+    )
+    .unwrap();
+    let mut events: Vec<Event> = Vec::new();
+    blocks[0].read_text(&test_state, &mut events);
+    assert_eq!(events[0], Event::Line(String::from("Strong!"), Color::White));
+    assert_eq!(blocks[0].available_options(&test_state).len(), 1);
+
+    for (line, column) in [("?- has_key and => Oops", 15), ("#- strength >> 2 => Oops", 14), ("?- (has_key => Oops", 12), ("?- has_key", 11)].iter() {
+        match parse_str("bad.txt", &format!(":- start\n{}", line)) {
+            Err(ParseError::Syntax { line, column: found, .. }) => assert_eq!((line, found), (2, *column)),
+            other => panic!("Expected a syntax error, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_counter_updates() {
+    let blocks = parse_str(
+        "test.txt",
+        ":- start\n+- health - damage * 2\n+- gold = stash\n+- gold * 3\n+- lives + 1",
+    )
+    .unwrap();
+    assert_eq!(blocks[0].body[0], Statement::Counter(String::from("health"), Update::Subtract(Expr::parse("damage * 2").unwrap())));
+
+    let manifest: Manifest = Manifest::parse(
+        "(\n    title: \"Test Story\",\n    start: (\"test.txt\", \"start\"),\n    variables: {\n        \"health\": Counter(default: 10, min: 0, max: 10),\n        \"damage\": Counter(default: 6),\n        \"gold\": Counter(),\n        \"stash\": Counter(default: 4),\n        \"lives\": Counter(max: 3),\n        \"has_key\": Flag(default: true),\n    },\n)",
+    )
+    .unwrap();
+    let mut test_state: GameState = GameState::from_manifest(&manifest);
+    assert!(test_state.get_flag("has_key"));
+    test_state.set_counter("lives", 3);
+
+    let mut events: Vec<Event> = Vec::new();
+    blocks[0].read(&mut test_state, &mut events);
+    assert_eq!(test_state.get_counter("health"), 0);
+    assert_eq!(test_state.get_counter("gold"), 12);
+    assert_eq!(test_state.get_counter("lives"), 3);
+
+    let mut loader: Loader = Loader::from_files(vec![("test.txt", ":- start\n+- health - 1\n=- has_key = false\n=- health = true")]);
+    loader.variables = manifest.variables;
+    match loader.load("test.txt") {
+        Err(ParseError::Syntax { line, message, .. }) => {
+            assert_eq!(line, 4);
+            assert_eq!(message, "`health` is declared as a counter, but is changed here as a flag");
+        }
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+
+    let mut option_loader: Loader = Loader::from_files(vec![("test.txt", ":- start\n*- Buy -> buy -> start -> +- gold - 1; +- gld - 1")]);
+    option_loader.variables = loader.variables.clone();
+    match option_loader.load("test.txt") {
+        Err(ParseError::Syntax { line, column, message, .. }) => {
+            assert_eq!((line, column), (2, 43));
+            assert_eq!(message, "counter `gld` isn't declared in the variables of story.ron");
+        }
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+
+    let mut read_loader: Loader = Loader::from_files(vec![("test.txt", ":- start\n*- Rob -> rob -> start -> +- gold = gold + bonus")]);
+    read_loader.variables = loader.variables.clone();
+    match read_loader.load("test.txt") {
+        Err(ParseError::Syntax { line, column, message, .. }) => {
+            assert_eq!((line, column), (2, 44));
+            assert_eq!(message, "counter `bonus` isn't declared in the variables of story.ron");
+        }
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+
+    match parse_str("bad.txt", ":- start\n+- health / 2") {
+        Err(ParseError::Syntax { column, .. }) => assert_eq!(column, 10),
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_prompts() {
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- start\nWelcome!\n$- player_name => What's your name? => nonempty, max 8\n$- companion => Who's with you?\n*- Go -> go -> end\n*- Stay -> stay -> end\n:- end\nBye!",
+    )]);
+    let mut test_state: GameState = GameState::new("Test_Prompts");
+    test_state.set_progress("test.txt", "start");
+
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+    let mut mock: Mock = Mock::new(&["", "Bartholomew", "Save", "", "2"]);
+    engine.run(&mut mock);
+
+    assert_eq!(engine.game.get_string("player_name"), "Save");
+    assert_eq!(engine.game.get_string("companion"), "");
+    assert!(mock.transcript.contains(&String::from("Please enter an answer.")));
+    assert!(mock.transcript.contains(&String::from("Please keep your answer to 8 characters or fewer.")));
+    assert_eq!(mock.transcript.iter().filter(|line| *line == "What's your name?").count(), 3);
+    assert_eq!(mock.transcript.last(), Some(&String::from("")));
+    assert!(engine.is_finished());
+
+    let blocks = parse_str("test.txt", ":- start\n$- player_name => What's your name? => nonempty").unwrap();
+    assert_eq!(
+        blocks[0].body,
+        vec![Statement::Prompt(Prompt {
+            variable: String::from("player_name"),
+            question: String::from("What's your name?"),
+            nonempty: true,
+            max_len: None,
+        })]
+    );
+
+    match parse_str("bad.txt", ":- start\n$- player_name => What's your name? => max twenty") {
+        Err(ParseError::Syntax { column, message, .. }) => {
+            assert_eq!(column, 44);
+            assert_eq!(message, "expected a whole number after `max`, found `twenty`");
+        }
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+    assert!(parse_str("bad.txt", ":- start\n$- player name => What's your name?").is_err());
+}
+
+#[test]
+fn test_interpolation() {
+    let mut test_state: GameState = GameState::new("Test_Interpolation");
+    test_state.set_string("player_name", "Sam");
+    test_state.update_counter("gold", 3);
+    test_state.set_flag("has_key", true);
+
+    assert_eq!(interpolate("{player_name} has {gold} {gold|coin|coins}.", &test_state).unwrap(), "Sam has 3 coins.");
+    assert_eq!(interpolate("[{gold:4}] [{gold:<4}] [{gold:03}] [{player_name:^5}]", &test_state).unwrap(), "[   3] [3   ] [003] [ Sam ]");
+    assert_eq!(interpolate("Key: {has_key}, {has_key|held|lost}", &test_state).unwrap(), "Key: true, held");
+    assert_eq!(interpolate("{{literal}}", &test_state).unwrap(), "{literal}");
+    assert_eq!(interpolate("Hi {nobody}", &test_state), Err(TemplateError { offset: 3, message: String::from("unknown variable `nobody`") }));
+    assert!(interpolate("{gold|coin}", &test_state).is_err());
+    assert!(interpolate("{gold:wide}", &test_state).is_err());
+
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- start\n$- player_name => Name?\n?- has_key => You hold {gold} {gold|coin|coins}.\n*- Pay {gold} -> pay -> end\n*- Keep {gold|it|them} -> keep -> end\n:- end\n-y Bye, {player_name}!\n{missing}",
+    )]);
+    let mut test_state: GameState = GameState::new("Test_Interpolation");
+    test_state.set_progress("test.txt", "start");
+    test_state.set_flag("has_key", true);
+    test_state.update_counter("gold", 1);
+
+    let mut engine: Engine = Engine::with_loader(test_state, loader.clone());
+    let mut events: Vec<Event> = engine.start().events;
+    events.extend(engine.step("Sam").events);
+
+    assert!(events.contains(&Event::Line(String::from("You hold 1 coin."), Color::White)));
+    assert!(events.contains(&Event::Options(vec![String::from("Pay 1"), String::from("Keep it")])));
+
+    let events: Vec<Event> = engine.step("pay 1").events;
+    assert!(events.contains(&Event::Line(String::from("Bye, Sam!"), Color::Yellow)));
+    assert!(events.contains(&Event::Line(String::from("Couldn't show \"{missing}\": unknown variable `missing`"), Color::Red)));
+
+    let mut game: GameState = GameState::new("Test_Interpolation");
+    game.set_progress("test.txt", "start");
+    let report: Report = check(&game, &loader);
+    let unknown: Vec<&Problem> = report.problems.iter().filter(|problem| problem.kind == ProblemKind::UnknownVariable).collect();
+    assert_eq!(unknown.len(), 5);
+    assert_eq!(unknown[0].line, Some(3));
+    assert_eq!(unknown[4].line, Some(8));
+    assert_eq!(unknown[4].message, "`{missing}` shows a variable that is never set");
+
+    match parse_str("bad.txt", ":- start\nYou have {gold coins.") {
+        Err(ParseError::Syntax { column, message, .. }) => {
+            assert_eq!(column, 10);
+            assert_eq!(message, "unclosed `{`, use `{{` to show a brace");
+        }
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_statement_order() {
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- start\n?- door_open => Too early.\n=- door_open = true\n?- door_open => The door is open.\n+- gold + 2\n+- gold + 3\n#- gold == 5 => You have 5 gold.\n$- player_name => Name?\nWelcome, {player_name}.\n*- Stay -> stay -> start\n*- Go -> go -> end\n:- end\nBye!",
+    )]);
+    let mut test_state: GameState = GameState::new("Test_Order");
+    test_state.set_progress("test.txt", "start");
+
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+    let events: Vec<Event> = engine.start().events;
+    assert_eq!(
+        events,
+        vec![
+            Event::Line(String::from("The door is open."), Color::White),
+            Event::Line(String::from("You have 5 gold."), Color::White),
+            Event::Prompt(String::from("Name?")),
+        ]
+    );
+    assert_eq!(engine.resume().events, vec![Event::Prompt(String::from("Name?"))]);
+
+    let events: Vec<Event> = engine.step("Sam").events;
+    assert_eq!(events[0], Event::Line(String::from("Welcome, Sam."), Color::White));
+    assert!(events.contains(&Event::Options(vec![String::from("Stay"), String::from("Go")])));
+    assert_eq!(engine.game.get_counter("gold"), 5);
+
+    engine.resume();
+    assert_eq!(engine.game.get_counter("gold"), 5);
+    engine.step("stay");
+    assert_eq!(engine.game.get_counter("gold"), 10);
+}
+
+#[test]
+fn test_sections() {
+    let story: &str = ":- start
+if- has_key
+You unlock the door.
+=- door_open = true
+if- gold > 5
+-y You pocket the gold inside.
+endif-
+*- Go inside -> inside -> end
+elif- strength > 10
+You break the door down.
+else-
+The door is locked.
+endif-
+switch- gold
+case- 0
+You are broke.
+case- 1, 2, 3
+You have a few coins.
+default-
+You have {gold} coins.
+endswitch-
+?- door_open => The way is open.
+*- Leave -> leave -> end
+:- end
+Bye!";
+
+    let mut test_state: GameState = GameState::new("Test_Sections");
+    test_state.set_progress("test.txt", "start");
+    test_state.set_flag("has_key", true);
+    test_state.update_counter("gold", 9);
+
+    let loader: Loader = Loader::from_files(vec![("test.txt", story)]);
+    let mut engine: Engine = Engine::with_loader(test_state, loader.clone());
+    assert_eq!(
+        engine.start().events,
+        vec![
+            Event::Line(String::from("You unlock the door."), Color::White),
+            Event::Line(String::from("You pocket the gold inside."), Color::Yellow),
+            Event::Line(String::from("You have 9 coins."), Color::White),
+            Event::Line(String::from("The way is open."), Color::White),
+            Event::Break,
+            Event::Options(vec![String::from("Go inside"), String::from("Leave")]),
+        ]
+    );
+
+    let mut test_state: GameState = GameState::new("Test_Sections");
+    test_state.set_progress("test.txt", "start");
+    test_state.update_counter("gold", 2);
+
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+    let events: Vec<Event> = engine.start().events;
+    assert_eq!(events[0], Event::Line(String::from("The door is locked."), Color::White));
+    assert_eq!(events[1], Event::Line(String::from("You have a few coins."), Color::White));
+    assert!(engine.is_finished());
+
+    let blocks = parse_str("test.txt", story).unwrap();
+    assert_eq!(blocks[0].choices().len(), 2);
+    assert_eq!(blocks[0].text().len(), 8);
+
+    for (contents, line, message) in [
+        (":- start\nif- has_key\nHello!", 2, "`if-` is never closed with `endif-`"),
+        (":- start\nHello!\nendif-", 3, "`endif-` without an open `if-`"),
+        (":- start\nif- has_key\nelse-\nelif- gold > 1\nendif-", 4, "`elif-` can't come after `else-`"),
+        (":- start\nswitch- gold\nif- has_key\nendswitch-", 3, "expected `case-` or `default-` after the `switch-` on line 2"),
+        (":- start\nif- has_key\ncase- 1\nendif-", 3, "expected `endif-` to close the `if-` on line 2, found `case-`"),
+        (":- start\nif- has_key\n:- next\nendif-", 3, "expected `endif-` to close the `if-` on line 2 before the next block"),
+        (":- start\nif- has_key and\nendif-", 2, "expected a number, variable, or `(`, found the end of the condition"),
+    ]
+    .iter()
+    {
+        match parse_str("bad.txt", contents) {
+            Err(ParseError::Syntax { line: found, message: found_message, .. }) => {
+                assert_eq!((found, found_message.as_str()), (*line, *message))
+            }
+            other => panic!("Expected a syntax error, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_visits() {
+    let loader: Loader = Loader::from_files(vec![
+        (
+            "test.txt",
+            ":- start\n?- first_visit => You arrive. => You're back.\n?- visited(cellar) => You smell the cellar on you.\n#- visits(next.txt#hall) > 1 => The hall again?\n*- Cellar -> cellar -> cellar\n*- Hall -> hall -> next.txt#hall\n:- cellar\nDamp.\n-> start",
+        ),
+        ("next.txt", ":- hall\nA long hall.\n-> test.txt#start"),
+    ]);
+    let mut test_state: GameState = GameState::new("Test_Visits");
+    test_state.set_progress("test.txt", "start");
+
+    let mut engine: Engine = Engine::with_loader(test_state, loader.clone());
+    assert_eq!(engine.start().events[0], Event::Line(String::from("You arrive."), Color::White));
+
+    let events: Vec<Event> = engine.step("cellar").events;
+    assert!(events.contains(&Event::Line(String::from("You're back."), Color::White)));
+    assert!(events.contains(&Event::Line(String::from("You smell the cellar on you."), Color::White)));
+    assert_eq!(engine.game.get_visits("start"), 2);
+    assert_eq!(engine.game.get_visits("cellar"), 1);
+
+    engine.resume();
+    assert_eq!(engine.game.get_visits("start"), 2);
+
+    assert!(!engine.step("hall").events.contains(&Event::Line(String::from("The hall again?"), Color::White)));
+    assert!(engine.step("hall").events.contains(&Event::Line(String::from("The hall again?"), Color::White)));
+    assert_eq!(engine.game.visits.get("next.txt#hall"), Some(&2));
+
+    let saved: GameState = ron::de::from_str(&ron::ser::to_string(&engine.game).unwrap()).unwrap();
+    assert_eq!(saved.visits, engine.game.visits);
+    let old_save: GameState = ron::de::from_str("(name: \"Old\", progress: (\"\", \"\"), flags: {}, counters: {})").unwrap();
+    assert!(old_save.visits.is_empty());
+
+    assert!(Expr::parse("visits(cellar").is_err());
+    assert!(Expr::parse("visited()").is_err());
+    assert!(Expr::parse("chapter2.txt#cellar > 1").is_err());
+    assert_eq!(Expr::parse("visits > 1").unwrap().counters(), vec!["visits"]);
+
+    let mut game: GameState = GameState::new("Test_Visits");
+    game.set_progress("test.txt", "start");
+    let loader: Loader = Loader::from_files(vec![("test.txt", ":- start\n?- visited(celar) => Typo.")]);
+    let report: Report = check(&game, &loader);
+    assert_eq!(report.problems[0].message, "visits of block `celar` are checked, but it doesn't exist");
+}
+
+#[test]
+fn test_sequences() {
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- hub\n~- stopping => Quiet. => Still quiet.\n~- cycle => Tick. => Tock.\n~- once => -y A bird sings.\n~- shuffle => Red. => Blue. => Green.\n*- Wait -> wait -> hub\n*- Leave -> leave -> end\n:- end\nBye.",
+    )]);
+    let mut test_state: GameState = GameState::new("Test_Sequences");
+    test_state.set_progress("test.txt", "hub");
+
+    let lines = |events: Vec<Event>| -> Vec<String> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Line(text, _) => Some(text),
+                _ => None,
             })
-        );
+            .collect()
+    };
 
-        let mut test_flags: HashMap<String, bool> = HashMap::new();
-        test_flags.insert(String::from("test_condition"), false);
-        assert_eq!(test_blocks[1].flags, test_flags);
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+    let first: Vec<String> = lines(engine.start().events);
+    assert_eq!(first[..3], ["Quiet.", "Tick.", "A bird sings."]);
 
-        let test_counters: HashMap<String, i32> = HashMap::new();
-        assert_eq!(test_blocks[1].counters, test_counters);
-    } else {
-        panic!("Couldn't load test.txt into StoryBlocks");
+    let second: Vec<String> = lines(engine.step("wait").events);
+    assert_eq!(second[..2], ["Still quiet.", "Tock."]);
+    let third: Vec<String> = lines(engine.step("wait").events);
+    assert_eq!(third[..2], ["Still quiet.", "Tick."]);
+
+    let mut colors: Vec<&str> = vec![first[3].as_str(), second[2].as_str(), third[2].as_str()];
+    colors.sort_unstable();
+    assert_eq!(colors, vec!["Blue.", "Green.", "Red."]);
+
+    let resumed: Vec<String> = lines(engine.resume().events);
+    assert_eq!(resumed[..3], ["Still quiet.", "Tick.", third[2].as_str()]);
+    assert_eq!(engine.game.sequences.get("test.txt#hub~0"), Some(&3));
+    assert_eq!(engine.game.sequences.get("test.txt#hub~2"), Some(&3));
+
+    let saved: GameState = ron::de::from_str(&ron::ser::to_string(&engine.game).unwrap()).unwrap();
+    assert_eq!(saved.sequences, engine.game.sequences);
+    let old_save: GameState = ron::de::from_str("(name: \"Old\", progress: (\"\", \"\"), flags: {}, counters: {})").unwrap();
+    assert!(old_save.sequences.is_empty());
+
+    let blocks = parse_str("test.txt", ":- start\n~- once => A.\nif- lit\n~- cycle => B. => C.\nendif-\n~- once => D.").unwrap();
+    let indices: Vec<usize> = blocks[0]
+        .statements()
+        .into_iter()
+        .filter_map(|statement| match statement {
+            Statement::Sequence(sequence) => Some(sequence.index),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(indices, vec![0, 1, 2]);
+
+    for (line, column) in [("~- sometimes => A.", 4), ("~- once", 8), ("~- once => A. => ", 18), ("~- once => ?- lit => A.", 12)].iter() {
+        match parse_str("bad.txt", &format!(":- start\n{}", line)) {
+            Err(ParseError::Syntax { column: found, .. }) => assert_eq!(found, *column, "{}", line),
+            _ => panic!("`{}` should not parse", line),
+        }
+    }
+
+    let mut game: GameState = GameState::new("Test_Sequences");
+    game.set_progress("test.txt", "start");
+    let loader: Loader = Loader::from_files(vec![("test.txt", ":- start\n~- cycle => Hi {nobody}. => Bye.")]);
+    let report: Report = check(&game, &loader);
+    assert_eq!(report.problems[0].message, "`{nobody}` shows a variable that is never set");
+
+    let shuffle = Sequence {
+        kind: SequenceKind::Shuffle,
+        lines: vec![String::from("A"), String::from("B"), String::from("C"), String::from("D")],
+        index: 0,
+    };
+    let order = |seed: u64| -> Vec<&str> { (0..8).filter_map(|read| shuffle.line(read, "test.txt#hub~0", seed)).collect() };
+    assert_eq!(order(42), vec!["B", "C", "D", "A", "A", "C", "B", "D"]);
+    assert_eq!(order(7), vec!["B", "A", "C", "D", "B", "D", "C", "A"]);
+
+    // Each new game is shuffled with its own seed, which is kept in its saves
+    let game: GameState = GameState::new("Test_Sequences");
+    assert_ne!(game.seed, GameState::new("Test_Sequences").seed);
+    let saved: GameState = ron::de::from_str(&ron::ser::to_string(&game).unwrap()).unwrap();
+    assert_eq!(saved.seed, game.seed);
+}
+
+#[test]
+fn test_choice_kinds() {
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- hub\nWhat do you ask?\n*- [once] Ask about the key -> key -> key\n*- [once] ?- curious => Ask about the door -> door -> door\n*- [sticky] Look around -> look -> hub\n*- Wait -> wait -> hub\n*- [fallback] Say goodbye -> bye -> bye\n:- key\nIt's old.\n-> hub\n:- door\nIt's locked.\n-> hub\n:- bye\nGoodbye.",
+    )]);
+    let mut test_state: GameState = GameState::new("Test_Choice_Kinds");
+    test_state.set_progress("test.txt", "hub");
+
+    let options = |events: &[Event]| -> Vec<String> {
+        events
+            .iter()
+            .find_map(|event| match event {
+                Event::Options(options) => Some(options.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    };
+
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+    assert_eq!(options(&engine.start().events), vec!["Ask about the key", "Look around", "Wait"]);
+    assert_eq!(options(&engine.step("key").events), vec!["Look around", "Wait"]);
+    assert_eq!(engine.game.times_chosen("test.txt#hub*0"), 1);
+
+    engine.game.set_flag("curious", true);
+    assert_eq!(options(&engine.resume().events), vec!["Ask about the door", "Look around", "Wait"]);
+    assert_eq!(options(&engine.step("door").events), vec!["Look around", "Wait"]);
+    assert_eq!(options(&engine.step("look").events), vec!["Look around", "Wait"]);
+
+    let saved: GameState = ron::de::from_str(&ron::ser::to_string(&engine.game).unwrap()).unwrap();
+    assert_eq!(saved.chosen, engine.game.chosen);
+    let old_save: GameState = ron::de::from_str("(name: \"Old\", progress: (\"\", \"\"), flags: {}, counters: {})").unwrap();
+    assert!(old_save.chosen.is_empty());
+
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- hub\n*- [once] Ask -> ask -> hub\n*- [once] Tell -> tell -> hub\n*- [fallback] Leave -> leave -> bye\n:- bye\nGoodbye.",
+    )]);
+    let mut test_state: GameState = GameState::new("Test_Choice_Kinds");
+    test_state.set_progress("test.txt", "hub");
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+    assert_eq!(options(&engine.start().events), vec!["Ask", "Tell"]);
+    let events: Vec<Event> = engine.step("ask").events;
+    assert!(events.contains(&Event::Line(String::from("Goodbye."), Color::White)));
+    assert_eq!(engine.game.times_chosen("test.txt#hub*1"), 1);
+    assert!(engine.is_finished());
+
+    let blocks = parse_str("test.txt", ":- start\n-> next\nif- lit\n*- [fallback] ?- tired => Rest -> rest -> next\nendif-\n*- Go -> go -> next\n:- next").unwrap();
+    let choices: Vec<(ChoiceKind, usize, &str)> =
+        blocks[0].choices().iter().map(|choice| (choice.kind, choice.index, choice.text.as_str())).collect();
+    assert_eq!(choices, vec![(ChoiceKind::Sticky, 0, ""), (ChoiceKind::Fallback, 1, "Rest"), (ChoiceKind::Sticky, 2, "Go")]);
+    assert_eq!(blocks[0].choices()[1].condition, Some(Expr::parse("tired").unwrap()));
+
+    let mut game: GameState = GameState::new("Test_Choice_Kinds");
+    game.set_progress("test.txt", "start");
+    let loader: Loader = Loader::from_files(vec![("test.txt", ":- start\n*- [once] ?- tird => Rest -> rest -> start")]);
+    let report: Report = check(&game, &loader);
+    assert_eq!(report.problems[0].message, "flag `tird` is checked, but never set");
+}
+
+#[test]
+fn test_choice_effects() {
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- shop\nYou have {gold} gold.\n*- ?- not has_sword => Buy the sword -> buy -> shop -> +- gold - 10; =- has_sword = true\n*- Haggle -> haggle -> shop -> +- gold = gold + discount\n*- Leave -> leave -> end\n:- end\n?- has_sword => You leave armed.",
+    )]);
+    let mut test_state: GameState = GameState::new("Test_Choice_Effects");
+    test_state.set_progress("test.txt", "shop");
+    test_state.set_counter("gold", 15);
+    test_state.set_counter("discount", 2);
+
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+    engine.start();
+    let events: Vec<Event> = engine.step("buy").events;
+    assert_eq!(events[0], Event::Line(String::from("You have 5 gold."), Color::White));
+    assert!(engine.game.get_flag("has_sword"));
+
+    engine.step("haggle");
+    assert_eq!(engine.game.get_counter("gold"), 7);
+    engine.resume();
+    assert_eq!(engine.game.get_counter("gold"), 7);
+
+    assert!(engine.step("leave").events.contains(&Event::Line(String::from("You leave armed."), Color::White)));
+
+    for (line, column) in [
+        ("*- Go -> go -> start -> gold + 1", 25),
+        ("*- Go -> go -> start -> +- gold + 1; =- lit = maybe", 47),
+        ("*- Go -> go -> start -> +- gold + 1 -> more", 40),
+    ]
+    .iter()
+    {
+        match parse_str("bad.txt", &format!(":- start\n{}", line)) {
+            Err(ParseError::Syntax { column: found, .. }) => assert_eq!(found, *column, "{}", line),
+            _ => panic!("`{}` should not parse", line),
+        }
+    }
+
+    let mut game: GameState = GameState::new("Test_Choice_Effects");
+    game.set_progress("test.txt", "start");
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- start\n?- lit => Bright.\n*- Light the lamp -> light -> start -> =- lit = true\n*- Wait -> wait -> start",
+    )]);
+    let report: Report = check(&game, &loader);
+    assert!(report.problems.iter().all(|problem| problem.kind != ProblemKind::UnsetFlag));
+
+    let loader: Loader = Loader::from_files(vec![(
+        "test.txt",
+        ":- start\n+- gold = gold + bonsu\n*- Rob -> rob -> start -> +- gold + loot * 2\n*- Wait -> wait -> start",
+    )]);
+    let report: Report = check(&game, &loader);
+    let unset: Vec<(Option<usize>, &str)> = report
+        .problems
+        .iter()
+        .filter(|problem| problem.kind == ProblemKind::UnsetCounter)
+        .map(|problem| (problem.line, problem.message.as_str()))
+        .collect();
+    assert_eq!(unset, vec![(Some(2), "counter `bonsu` is read, but never set"), (Some(3), "counter `loot` is read, but never set")]);
+
+    // A name on its own in a counter's value is a counter, and a truth value compared in it is a flag
+    let blocks = parse_str("test.txt", ":- start\n+- gold = stash\n+- gold + (lucky == true) * 5").unwrap();
+    match (&blocks[0].body[0], &blocks[0].body[1]) {
+        (Statement::Counter(_, copy), Statement::Counter(_, bonus)) => {
+            assert_eq!((copy.flags(), copy.counters()), (vec![], vec!["stash"]));
+            assert_eq!((bonus.flags(), bonus.counters()), (vec!["lucky"], vec![]));
+        }
+        other => panic!("Expected two counter updates, got {:?}", other),
     }
+    let loader: Loader = Loader::from_files(vec![("test.txt", ":- start\n+- gold + (lucky == true) * 5\n*- Wait -> wait -> start")]);
+    let report: Report = check(&game, &loader);
+    let unset: Vec<(ProblemKind, &str)> = report
+        .problems
+        .iter()
+        .filter(|problem| problem.kind == ProblemKind::UnsetFlag || problem.kind == ProblemKind::UnsetCounter)
+        .map(|problem| (problem.kind, problem.message.as_str()))
+        .collect();
+    assert_eq!(unset, vec![(ProblemKind::UnsetFlag, "flag `lucky` is read, but never set")]);
 }
+
+#[test]
+fn test_conditional_targets() {
+    let loader: Loader = Loader::from_files(vec![
+        (
+            "test.txt",
+            ":- start\n*- Take the key -> take -> ?- has_key => door => start -> =- has_key = true\n*- Wait -> wait -> start\n:- door\n-> #- gold >= 10 => next.txt#vault => locked\n:- locked\nThe door is locked.",
+        ),
+        ("next.txt", ":- vault\nGold everywhere."),
+    ]);
+    let new_state = |gold: i32| -> GameState {
+        let mut test_state: GameState = GameState::new("Test_Conditional_Targets");
+        test_state.set_progress("test.txt", "start");
+        test_state.set_counter("gold", gold);
+        test_state
+    };
+
+    let mut engine: Engine = Engine::with_loader(new_state(0), loader.clone());
+    engine.start();
+    assert!(engine.step("take").events.contains(&Event::Line(String::from("The door is locked."), Color::White)));
+    assert_eq!(engine.game.progress.1, "locked");
+
+    let mut engine: Engine = Engine::with_loader(new_state(10), loader.clone());
+    engine.start();
+    assert!(engine.step("take").events.contains(&Event::Line(String::from("Gold everywhere."), Color::White)));
+    assert_eq!(engine.game.progress, (String::from("next.txt"), String::from("vault")));
+
+    let story: Story = Story::load(&loader, &["test.txt"]).unwrap();
+    assert!(story.contains("next.txt"));
+    assert_eq!(targets("?- has_key => door => start"), vec!["door", "start"]);
+    let choice: &Choice = story.get("test.txt", "start").unwrap().choices()[0];
+    assert_eq!(choice.result, "?- has_key => door => start");
+    assert_eq!(choice.result_condition, Some(Expr::parse("has_key").unwrap()));
+
+    for (line, column) in [("-> ?- has_key => door", 22), ("-> ?- has_key => door => locked => start", 41), ("-> ?- has_key => door => chapter2#vault", 34)].iter() {
+        match parse_str("bad.txt", &format!(":- start\n{}", line)) {
+            Err(ParseError::Syntax { column: found, .. }) => assert_eq!(found, *column, "{}", line),
+            _ => panic!("`{}` should not parse", line),
+        }
+    }
+
+    let loader: Loader = Loader::from_files(vec![("test.txt", ":- start\n-> ?- has_kee => start => vault\n:- unused")]);
+    let report: Report = check(&new_state(0), &loader);
+    let messages: Vec<&str> = report.problems.iter().map(|problem| problem.message.as_str()).collect();
+    assert!(messages.contains(&"option leads to `vault`, which doesn't exist"));
+    assert!(messages.contains(&"flag `has_kee` is checked, but never set"));
+    assert_eq!(report.problems.iter().find(|problem| problem.kind == ProblemKind::MissingTarget).unwrap().line, Some(2));
+}
+
+#[test]
+fn test_save_slots() {
+    let mut game: GameState = GameState::new("Test_Save_Slots");
+    game.set_save_dir(std::env::temp_dir().join("intfic_test_save_slots"));
+    for info in game.slots().unwrap() {
+        game.delete_slot(&info.slot).unwrap();
+    }
+
+    game.set_progress("test.txt", "start");
+    game.excerpt = "It's nearly pitch black out tonight. ".repeat(3);
+    game.save_slot("1").unwrap();
+    game.set_counter("gold", 5);
+    game.save_slot("before the dragon").unwrap();
+
+    let slots: Vec<SlotInfo> = game.slots().unwrap();
+    let mut names: Vec<&str> = slots.iter().map(|info| info.slot.as_str()).collect();
+    names.sort_unstable();
+    assert_eq!(names, vec!["1", "before the dragon"]);
+    assert_eq!(slots[0].block, "test.txt#start");
+    assert_eq!(slots[0].excerpt.chars().count(), 60);
+    assert!(slots[0].excerpt.ends_with('…'));
+
+    game.set_progress("elsewhere.txt", "");
+    game.load_slot("1").unwrap();
+    assert_eq!(game.progress, (String::from("test.txt"), String::from("start")));
+    assert_eq!(game.get_counter("gold"), 0);
+    assert!(game.get_flag("saved"));
+
+    let mut mock: Mock = Mock::new(&["Delete Before the Dragon"]);
+    assert_eq!(check_keywords(String::from("load"), &mut game, &mut mock), None);
+    assert!(mock.transcript.iter().any(|line| line.contains(") before the dragon (saved just now")));
+    assert_eq!(game.slots().unwrap().len(), 1);
+
+    check_keywords(String::from("save"), &mut game, &mut Mock::new(&["1"]));
+    check_keywords(String::from("save"), &mut game, &mut Mock::new(&["Second Try!"]));
+    check_keywords(String::from("save"), &mut game, &mut Mock::new(&[""]));
+    let mut names: Vec<String> = game.slots().unwrap().into_iter().map(|info| info.slot).collect();
+    names.sort_unstable();
+    assert_eq!(names, vec!["1", "Second Try!"]);
+    assert!(std::env::temp_dir().join("intfic_test_save_slots").join("Test_Save_Slots").join("Second Try_.ron").exists());
+
+    game.set_counter("gold", 9);
+    check_keywords(String::from("load"), &mut game, &mut Mock::new(&["second try!"]));
+    assert_eq!(game.get_counter("gold"), 0);
+
+    for info in game.slots().unwrap() {
+        game.delete_slot(&info.slot).unwrap();
+    }
+    assert!(game.slots().unwrap().is_empty());
+
+    let info = SlotInfo {
+        slot: String::from("1"),
+        saved_at: 0,
+        block: String::from("test.txt#start"),
+        excerpt: String::from("Hello!"),
+        playtime: 3725,
+        version: SAVE_VERSION,
+        story_hash: 0,
+    };
+    assert!(info.to_string().starts_with("1 (saved "));
+    assert!(info.to_string().ends_with("days ago, played 1h 02m) at test.txt#start: \"Hello!\""));
+
+    let loader: Loader = Loader::from_files(vec![("test.txt", ":- start\nHello there.\n\n*- Wave -> wave -> start\n*- Leave -> leave -> start")]);
+    let mut test_state: GameState = GameState::new("Test_Save_Slots");
+    test_state.set_progress("test.txt", "start");
+    let mut engine: Engine = Engine::with_loader(test_state, loader);
+    engine.start();
+    assert_eq!(engine.game.excerpt, "Hello there.");
+}
+
+#[test]
+fn test_save_errors() {
+    let mut game: GameState = GameState::new("Test_Save_Errors");
+    game.set_save_dir(std::env::temp_dir().join("intfic_test_save_errors"));
+    let save_dir = FileStore::in_dir(std::env::temp_dir().join("intfic_test_save_errors"), Format::Ron).dir("Test_Save_Errors").unwrap();
+    std::fs::create_dir_all(&save_dir).unwrap();
+
+    assert_eq!(game.load_slot("empty"), Err(SaveError::Missing { slot: String::from("empty") }));
+    assert_eq!(game.delete_slot("empty"), Err(SaveError::Missing { slot: String::from("empty") }));
+
+    std::fs::write(save_dir.join("corrupt.ron"), "((slot: \"corrupt\", saved_at: 0,").unwrap();
+    game.set_counter("gold", 5);
+    assert!(matches!(game.load_slot("corrupt"), Err(SaveError::Deserialize { .. })));
+    assert_eq!(game.get_counter("gold"), 5);
+
+    let mut old: GameState = GameState::new("Test_Save_Errors");
+    old.set_save_dir(std::env::temp_dir().join("intfic_test_save_errors"));
+    old.save_slot("old").unwrap();
+    let contents: String = std::fs::read_to_string(save_dir.join("old.ron")).unwrap();
+    std::fs::write(save_dir.join("old.ron"), contents.replace("version: 2,", "version: 99,")).unwrap();
+    match game.load_slot("old") {
+        Err(SaveError::Version { slot, found, expected }) => assert_eq!((slot.as_str(), found, expected), ("old", 99, SAVE_VERSION)),
+        other => panic!("expected a version error, got {:?}", other),
+    }
+    assert_eq!(game.get_counter("gold"), 5);
+
+    let mut mock: Mock = Mock::new(&["corrupt"]);
+    check_keywords(String::from("load"), &mut game, &mut mock);
+    assert!(mock.transcript.iter().any(|line| line.starts_with("The save in slot \"corrupt\" isn't valid")));
+    assert!(mock.transcript.iter().any(|line| line.starts_with("1) old (saved just now")));
+
+    std::fs::remove_dir_all(&save_dir).unwrap();
+    assert_eq!(game.slots(), Ok(Vec::new()));
+}
+
+#[test]
+fn test_save_dir() {
+    let root = std::env::temp_dir().join("intfic_test_save_dir");
+    let mut game: GameState = GameState::new("Act 1: The Road/..");
+    game.set_save_dir(&root);
+    assert_eq!(FileStore::in_dir(&root, Format::Ron).dir(&game.name).unwrap(), root.join("Act 1_ The Road_"));
+
+    game.save_slot("../../escape").unwrap();
+    assert!(root.join("Act 1_ The Road_").join("_.._escape.ron").exists());
+    assert_eq!(game.slots().unwrap()[0].slot, "../../escape");
+    game.load_slot("../../escape").unwrap();
+    game.delete_slot("../../escape").unwrap();
+    assert!(!root.join("Act 1_ The Road_").join("_.._escape.ron").exists());
+
+    // Names that are only told apart by characters a file name can't hold don't replace each other
+    game.save_slot("a:b").unwrap();
+    assert_eq!(
+        game.save_slot("a/b"),
+        Err(SaveError::Conflict { slot: String::from("a/b"), existing: String::from("a:b") }),
+    );
+    game.save_slot("con").unwrap();
+    assert!(matches!(game.save_slot("con_"), Err(SaveError::Conflict { .. })));
+    let mut names: Vec<String> = game.slots().unwrap().into_iter().map(|info| info.slot).collect();
+    names.sort();
+    assert_eq!(names, vec!["a:b", "con"]);
+    assert_eq!(game.load_slot("a/b"), Err(SaveError::Missing { slot: String::from("a/b") }));
+    assert_eq!(game.delete_slot("con_"), Err(SaveError::Missing { slot: String::from("con_") }));
+    game.load_slot("a:b").unwrap();
+
+    // Saves from before games had save slots are found at their old location as the quicksave
+    let mut legacy: GameState = GameState::new("Test_Save_Dir");
+    legacy.set_counter("gold", 7);
+    std::fs::write(root.join("Test_Save_Dir.ron"), ron::ser::to_string(&legacy).unwrap()).unwrap();
+    let mut game: GameState = GameState::new("Test_Save_Dir");
+    game.set_save_dir(&root);
+    assert_eq!(game.slots().unwrap()[0].slot, QUICKSAVE);
+    game.load().unwrap();
+    assert_eq!(game.get_counter("gold"), 7);
+    assert!(FileStore::in_dir(&root, Format::Json).read("Test_Save_Dir", QUICKSAVE).is_err());
+    game.delete_slot(QUICKSAVE).unwrap();
+    assert!(!root.join("Test_Save_Dir.ron").exists());
+
+    // Even when the game's name isn't safe to use as a file name, since its save was named after it as it was
+    let mut legacy: GameState = GameState::new("The Dragon's Lair");
+    legacy.set_counter("gold", 3);
+    std::fs::write(root.join("The Dragon's Lair.ron"), ron::ser::to_string(&legacy).unwrap()).unwrap();
+    let mut game: GameState = GameState::new("The Dragon's Lair");
+    game.set_save_dir(&root);
+    game.load().unwrap();
+    assert_eq!(game.get_counter("gold"), 3);
+    game.delete_slot(QUICKSAVE).unwrap();
+    assert!(!root.join("The Dragon's Lair.ron").exists());
+
+    assert_eq!(default_root(Some(root.clone().into_os_string())).unwrap(), root);
+    assert_eq!(default_root(Some(std::ffi::OsString::new())).unwrap(), default_root(None).unwrap());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_save_migrations() {
+    let story = ":- start\nYou wake up.\n+- gold + 5\n=- has_key = true\n\n*- Go on -> on -> road\n*- Stay -> stay -> start\n\n:- road\nThe road goes on.\n\n*- Left -> left -> start\n*- Right -> right -> start";
+    let renamed = story.replace(":- road", ":- highway").replace("-> road", "-> highway").replace("gold", "coins").replace("has_key", "has_gate_key");
+    let files = |story: &str| Loader::from_files(vec![("story.ron", "(title: \"Test_Save_Migrations\", start: (\"test.txt\", \"start\"))"), ("test.txt", story)]);
+    let save_dir = std::env::temp_dir().join("intfic_test_save_migrations");
+
+    let mut engine: Engine = Engine::from_manifest(files(story)).unwrap();
+    engine.game.set_save_dir(&save_dir);
+    engine.start();
+    engine.step("go on");
+    assert_eq!(engine.game.checkpoints, vec!["test.txt#start", "test.txt#road"]);
+    engine.game.save_slot("1").unwrap();
+    assert_eq!(engine.game.slots().unwrap()[0].story_hash, engine.game.story_hash);
+
+    // The same story loads without any warnings
+    let mut same: Engine = Engine::from_manifest(files(story)).unwrap();
+    same.game.set_save_dir(&save_dir);
+    same.game.load_slot("1").unwrap();
+    assert_eq!(same.resume().events[0], Event::Line(String::from("The road goes on."), Color::White));
+
+    // A changed story with migrations carries on from the renamed block
+    let mut migrated: Engine = Engine::from_manifest(files(&renamed)).unwrap();
+    migrated.game.set_save_dir(&save_dir);
+    migrated.migrations.push(Migration::new().rename_block("test.txt#road", "test.txt#highway").rename_flag("has_key", "has_gate_key"));
+    migrated.migrations.push(Migration::new().rename_counter("gold", "coins"));
+    let mut mock: Mock = Mock::new(&["1"]);
+    assert_eq!(check_keywords(String::from("load"), &mut migrated.game, &mut mock), None);
+    let events: Vec<Event> = migrated.resume().events;
+    assert!(matches!(&events[0], Event::Line(line, Color::Yellow) if line.contains("different version of the story")));
+    assert_eq!(events[1], Event::Line(String::from("The road goes on."), Color::White));
+    assert_eq!(migrated.game.progress_ref(), "test.txt#highway");
+    assert_eq!(migrated.game.get_counter("coins"), 5);
+    assert!(migrated.game.get_flag("has_gate_key"));
+    assert!(migrated.game.visits.contains_key("test.txt#highway"));
+
+    // Without migrations, the player is warned and falls back to the nearest checkpoint still in the story
+    let mut fallback: Engine = Engine::from_manifest(files(&renamed)).unwrap();
+    fallback.game.set_save_dir(&save_dir);
+    fallback.game.load_slot("1").unwrap();
+    let events: Vec<Event> = fallback.resume().events;
+    assert!(events.contains(&Event::Line(
+        String::from("test.txt#road is no longer in the story, so you'll carry on from test.txt#start instead."),
+        Color::Red,
+    )));
+    assert_eq!(fallback.game.progress_ref(), "test.txt#start");
+    assert!(events.contains(&Event::Options(vec![String::from("Go on"), String::from("Stay")])));
+
+    // Saves from before slots had descriptions are upgraded as they're loaded
+    let game_dir = FileStore::in_dir(&save_dir, Format::Ron).dir("Test_Save_Migrations").unwrap();
+    std::fs::write(game_dir.join("ancient.ron"), "(name: \"Test_Save_Migrations\", progress: (\"test.txt\", \"road\"), flags: {}, counters: {\"gold\": 3})").unwrap();
+    let mut upgraded: Engine = Engine::from_manifest(files(story)).unwrap();
+    upgraded.game.set_save_dir(&save_dir);
+    upgraded.game.load_slot("ancient").unwrap();
+    assert_eq!(upgraded.game.loaded.as_ref().map(|info| info.version), Some(0));
+    assert_eq!(upgraded.resume().events[0], Event::Line(String::from("The road goes on."), Color::White));
+    assert_eq!(upgraded.game.get_counter("gold"), 3);
+
+    // So is the single save file a game had before save slots, written by GameState::save at the time
+    let legacy_save = "(\n    name: \"Test_Save_Migrations\",\n    progress: (\"test.txt\", \"road\"),\n    flags: {\n        \"saved\": true,\n    },\n    counters: {\n        \"score\": 0,\n        \"gold\": 8,\n    },\n)";
+    std::fs::write(save_dir.join("Test_Save_Migrations.ron"), legacy_save).unwrap();
+    let mut legacy: Engine = Engine::from_manifest(files(story)).unwrap();
+    legacy.game.set_save_dir(&save_dir);
+    assert!(legacy.game.slots().unwrap().iter().any(|info| info.slot == QUICKSAVE && info.version == 0));
+    legacy.game.load().unwrap();
+    assert_eq!(legacy.resume().events[0], Event::Line(String::from("The road goes on."), Color::White));
+    assert_eq!(legacy.game.get_counter("gold"), 8);
+    assert!(legacy.game.get_flag("saved"));
+
+    std::fs::remove_dir_all(&save_dir).unwrap();
+}
+
+#[test]
+fn test_save_stores() {
+    let root = std::env::temp_dir().join("intfic_test_save_stores");
+    let mut game: GameState = GameState::new("Test_Save_Stores");
+    game.set_progress("test.txt", "start");
+    game.set_counter("gold", 5);
+    game.set_string("player_name", "Sam");
+
+    for format in [Format::Ron, Format::Json, Format::Binary] {
+        game.set_store(FileStore::in_dir(&root, format));
+        game.save_slot("1").unwrap();
+        game.save_slot("2").unwrap();
+        assert!(root.join("Test_Save_Stores").join(format!("1.{}", format.extension())).exists());
+
+        let mut loaded: GameState = GameState::new("Test_Save_Stores");
+        loaded.set_store(FileStore::in_dir(&root, format));
+        assert_eq!(loaded.slots().unwrap().len(), 2);
+        loaded.load_slot("1").unwrap();
+        assert_eq!(loaded.progress_ref(), "test.txt#start");
+        assert_eq!(loaded.get_counter("gold"), 5);
+        assert_eq!(loaded.get_string("player_name"), "Sam");
+
+        let mut newer: SlotInfo = SlotInfo::new("newer", &game);
+        newer.version = SAVE_VERSION + 1;
+        std::fs::write(root.join("Test_Save_Stores").join(format!("newer.{}", format.extension())), format.encode(&newer, &game).unwrap()).unwrap();
+        assert!(matches!(loaded.load_slot("newer"), Err(SaveError::Version { .. })));
+
+        std::fs::write(root.join("Test_Save_Stores").join(format!("corrupt.{}", format.extension())), b"\xff\xff\xff\xff\xff\xff\xff\xff\xff").unwrap();
+        assert!(matches!(loaded.load_slot("corrupt"), Err(SaveError::Deserialize { .. })));
+        assert_eq!(loaded.slots().unwrap().len(), 3);
+    }
+    // A binary save in a newer version is recognised before anything else in it is read
+    let mut newer: Vec<u8> = bincode::DefaultOptions::new().serialize(&(SAVE_VERSION + 1)).unwrap();
+    newer.extend_from_slice(b"\xff\xff\xff\xff");
+    match Format::Binary.decode("newer", &newer) {
+        Err(SaveError::Version { slot, found, .. }) => assert_eq!((slot.as_str(), found), ("newer", SAVE_VERSION + 1)),
+        other => panic!("expected a version error, got {:?}", other),
+    }
+
+    let contents: String = std::fs::read_to_string(root.join("Test_Save_Stores").join("1.json")).unwrap();
+    assert!(serde_json::from_str::<serde_json::Value>(&contents).is_ok());
+    std::fs::remove_dir_all(&root).unwrap();
+
+    let store: MemoryStore = MemoryStore::new();
+    let mut memory: GameState = GameState::new("Test_Save_Stores");
+    memory.set_store(store.clone());
+    memory.set_counter("gold", 9);
+    memory.save().unwrap();
+    memory.set_counter("gold", 0);
+    memory.load().unwrap();
+    assert_eq!(memory.get_counter("gold"), 9);
+    assert_eq!(store.list("Test_Save_Stores").unwrap()[0].slot, QUICKSAVE);
+    assert!(store.list("Another Game").unwrap().is_empty());
+
+    let mut mock: Mock = Mock::new(&["delete 1"]);
+    check_keywords(String::from("load"), &mut memory, &mut mock);
+    assert!(mock.transcript.contains(&String::from("Save Deleted!")));
+    assert_eq!(store.read("Test_Save_Stores", QUICKSAVE), Err(SaveError::Missing { slot: String::from(QUICKSAVE) }));
+    assert!(!root.exists());
+}
+
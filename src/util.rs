@@ -0,0 +1,22 @@
+// Hashes the given bytes with FNV-1a, which unlike the standard library's hasher gives the same result on every platform and Rust version.
+pub(crate) fn stable_hash<I: IntoIterator<Item = u8>>(bytes: I) -> u64 {
+    bytes
+        .into_iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+// Returns the numbers from 0 up to the given length in an order picked by the given seed.
+//
+// A Fisher-Yates shuffle driven by SplitMix64, so the same seed gives the same order on every platform and version of our dependencies.
+pub(crate) fn shuffled(len: usize, mut seed: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..len).collect();
+    for i in (1..len).rev() {
+        seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z: u64 = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        order.swap(i, (z % (i as u64 + 1)) as usize);
+    }
+    order
+}
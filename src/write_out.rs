@@ -7,7 +7,7 @@ use std::time::Duration;
 use crate::{FASTMODE, LINETIME, TYPETIME};
 
 /// Represents the available text colors we can output.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Color {
     /// ![Blue](https://via.placeholder.com/16/0000ff/000000?text=+)
     Blue,